@@ -0,0 +1,8 @@
+pub struct Shots {}
+
+impl Shots {
+    pub const MAX_SHOT_TEMPERATURE: f32 = 105.0;
+    pub const MIN_SHOT_TEMPERATURE: f32 = 00.0;
+    pub const MAX_SHOT_PRESSURE_BAR: f32 = 12.0;
+    pub const MIN_SHOT_PRESSURE_BAR: f32 = 3.0;
+}
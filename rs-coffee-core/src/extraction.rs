@@ -0,0 +1,209 @@
+use crate::types::{Bar, Grams, Temperature};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Below these the readings are mostly sensor noise
+const FIRST_DRIP_WEIGHT: Grams = 0.5;
+const CONTACT_PRESSURE: Bar = 0.5;
+const MIN_FLOW: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Reading {
+    pub temperature: Temperature,
+    pub pressure: Bar,
+    pub weight: Grams,
+    pub flow: f32,
+}
+
+// Times are in seconds from the start of the shot, flow in g/s
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+pub struct Metrics {
+    pub first_drip: Option<f32>,
+    pub contact_time: f32,
+    pub brew_ratio: Option<f32>,
+    pub peak_pressure: Bar,
+    pub mean_pressure: Bar,
+    pub mean_flow: f32,
+    // Pressure over flow squared, the same measure the DE1 plots
+    pub resistance: Option<f32>,
+    // Standard deviation of the boiler temperature around the target while water was on the puck
+    pub temperature_deviation: Option<Temperature>,
+}
+
+fn mean(values: impl Iterator<Item = f32>) -> Option<f32> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then_some(sum / count as f32)
+}
+
+pub fn brew_ratio(yield_weight: Grams, dose: Option<Grams>) -> Option<f32> {
+    dose.filter(|dose| *dose > 0.0)
+        .map(|dose| yield_weight / dose)
+}
+
+impl Metrics {
+    pub fn compute(
+        readings: &[Reading],
+        interval: Duration,
+        target: Option<Temperature>,
+        dose: Option<Grams>,
+    ) -> Self {
+        let seconds = |index: usize| interval.as_secs_f32() * index as f32;
+        // From the first reading with pressure on the puck to the last, the log carries on
+        // after the pump stops while the cup is taken away
+        let under_pressure = |r: &Reading| r.pressure >= CONTACT_PRESSURE;
+        let contact = match (
+            readings.iter().position(under_pressure),
+            readings.iter().rposition(under_pressure),
+        ) {
+            (Some(start), Some(end)) => &readings[start..=end],
+            _ => &readings[..0],
+        };
+        let drip = readings.iter().position(|r| r.weight >= FIRST_DRIP_WEIGHT);
+        let dripping = drip.map_or(&readings[..0], |start| &readings[start..]);
+
+        Metrics {
+            first_drip: drip.map(seconds),
+            contact_time: seconds(contact.len()),
+            brew_ratio: brew_ratio(readings.last().map(|r| r.weight).unwrap_or_default(), dose),
+            peak_pressure: readings.iter().map(|r| r.pressure).fold(0.0, f32::max),
+            mean_pressure: mean(contact.iter().map(|r| r.pressure)).unwrap_or_default(),
+            mean_flow: mean(dripping.iter().map(|r| r.flow)).unwrap_or_default(),
+            resistance: mean(
+                dripping
+                    .iter()
+                    .filter(|r| r.flow >= MIN_FLOW)
+                    .map(|r| r.pressure / (r.flow * r.flow)),
+            ),
+            temperature_deviation: target.and_then(|target| {
+                mean(contact.iter().map(|r| (r.temperature - target).powi(2))).map(f32::sqrt)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    fn reading(pressure: Bar, weight: Grams, flow: f32) -> Reading {
+        Reading {
+            temperature: 93.0,
+            pressure,
+            weight,
+            flow,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn brew_ratio_needs_a_dose() {
+        for (yield_weight, dose, expected) in [
+            (36.0, Some(18.0), Some(2.0)),
+            (40.0, Some(16.0), Some(2.5)),
+            (36.0, Some(0.0), None),
+            (36.0, Some(-1.0), None),
+            (36.0, None, None),
+        ] {
+            assert_eq!(brew_ratio(yield_weight, dose), expected, "{dose:?}");
+        }
+    }
+
+    #[test]
+    fn empty_log() {
+        let metrics = Metrics::compute(&[], INTERVAL, Some(93.0), Some(18.0));
+        assert_eq!(
+            metrics,
+            Metrics {
+                brew_ratio: Some(0.0),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn no_pressure_means_no_contact() {
+        let readings = [reading(0.0, 0.0, 0.0), reading(0.4, 1.0, 1.0)];
+        let metrics = Metrics::compute(&readings, INTERVAL, Some(93.0), None);
+        assert_eq!(metrics.contact_time, 0.0);
+        assert_eq!(metrics.mean_pressure, 0.0);
+        assert_eq!(metrics.temperature_deviation, None);
+        assert!(close(metrics.peak_pressure, 0.4));
+    }
+
+    #[test]
+    fn cup_never_reaches_the_drip_weight() {
+        let readings = [reading(9.0, 0.0, 0.0), reading(9.0, 0.4, 0.8)];
+        let metrics = Metrics::compute(&readings, INTERVAL, None, Some(18.0));
+        assert_eq!(metrics.first_drip, None);
+        assert_eq!(metrics.mean_flow, 0.0);
+        assert_eq!(metrics.resistance, None);
+        assert!(close(metrics.contact_time, 1.0));
+    }
+
+    #[test]
+    fn slow_flow_has_no_resistance() {
+        let readings = [reading(9.0, 1.0, 0.2), reading(9.0, 1.2, 0.4)];
+        let metrics = Metrics::compute(&readings, INTERVAL, None, None);
+        assert_eq!(metrics.first_drip, Some(0.0));
+        assert!(close(metrics.mean_flow, 0.3));
+        assert_eq!(metrics.resistance, None);
+    }
+
+    #[test]
+    fn resistance_only_counts_real_flow() {
+        let readings = [reading(8.0, 1.0, 0.1), reading(8.0, 3.0, 2.0)];
+        let metrics = Metrics::compute(&readings, INTERVAL, None, None);
+        assert!(close(metrics.resistance.unwrap(), 2.0));
+    }
+
+    #[test]
+    fn contact_window() {
+        // (pressures, contact readings, mean pressure over them)
+        for (pressures, contact, mean_pressure) in [
+            // Pump stops and the log carries on while the cup is taken away
+            (vec![0.0, 2.0, 9.0, 9.0, 0.0, 0.0], 3, 20.0 / 3.0),
+            // A dip in the middle is still water on the puck
+            (vec![0.0, 9.0, 0.2, 9.0, 0.0], 3, 18.2 / 3.0),
+            // Under pressure from the first reading to the last
+            (vec![1.0, 9.0, 9.0, 1.0], 4, 5.0),
+            (vec![0.0, 0.0, 6.0], 1, 6.0),
+        ] {
+            let readings: Vec<Reading> = pressures
+                .iter()
+                .map(|pressure| reading(*pressure, 0.0, 0.0))
+                .collect();
+            let metrics = Metrics::compute(&readings, INTERVAL, None, None);
+            assert!(
+                close(metrics.contact_time, contact as f32 * 0.5),
+                "{pressures:?}: {}",
+                metrics.contact_time
+            );
+            assert!(close(metrics.mean_pressure, mean_pressure), "{pressures:?}");
+        }
+    }
+
+    #[test]
+    fn temperature_deviation_over_contact() {
+        let mut readings = [
+            reading(0.0, 0.0, 0.0),
+            reading(9.0, 0.0, 0.0),
+            reading(9.0, 0.0, 0.0),
+            reading(0.0, 0.0, 0.0),
+        ];
+        readings[0].temperature = 80.0;
+        readings[1].temperature = 92.0;
+        readings[2].temperature = 94.0;
+        readings[3].temperature = 80.0;
+        let metrics = Metrics::compute(&readings, INTERVAL, Some(93.0), None);
+        assert!(close(metrics.temperature_deviation.unwrap(), 1.0));
+        assert_eq!(
+            Metrics::compute(&readings, INTERVAL, None, None).temperature_deviation,
+            None
+        );
+    }
+}
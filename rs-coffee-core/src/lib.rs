@@ -0,0 +1,7 @@
+pub mod config;
+pub mod extraction;
+pub mod pt100;
+pub mod schemas;
+pub mod screens;
+pub mod switches;
+pub mod types;
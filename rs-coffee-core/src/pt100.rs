@@ -0,0 +1,271 @@
+use crate::types::Temperature;
+use serde::{Deserialize, Serialize};
+
+// Callendar–Van Dusen coefficients for IEC 60751 platinum RTDs
+const R0: f64 = 100.0;
+const A: f64 = 3.9083e-3;
+const B: f64 = -5.775e-7;
+const C: f64 = -4.183e-12;
+
+const MIN_TEMPERATURE: f64 = -200.0;
+const MAX_TEMPERATURE: f64 = 850.0;
+const NEWTON_ITERATIONS: usize = 8;
+const REPLACE_POINT_WITHIN: Temperature = 5.0;
+// About 5C on a PT100, points closer than this leave the fit dividing by next to nothing
+const MIN_POINT_GAP: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct CalibrationPoint {
+    pub reading: f32,
+    pub reference: Temperature,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Default, PartialEq)]
+pub struct Calibration {
+    pub points: [Option<CalibrationPoint>; 3],
+}
+
+impl Calibration {
+    // `to_resistance` turns a raw reading into ohms, the same as for `correct`
+    pub fn add_point(
+        &mut self,
+        point: CalibrationPoint,
+        to_resistance: impl Fn(f64) -> f64,
+    ) -> Result<(), String> {
+        let resistance = to_resistance(point.reading as f64);
+        if !point.reference.is_finite() || !resistance.is_finite() {
+            return Err("Calibration point must have a finite reading and reference".to_string());
+        }
+
+        let slot = self
+            .points
+            .iter()
+            .position(|p| {
+                p.map(|p| (p.reference - point.reference).abs() < REPLACE_POINT_WITHIN)
+                    .unwrap_or(false)
+            })
+            .or_else(|| self.points.iter().position(|p| p.is_none()))
+            .ok_or("Calibration already has three points, clear it first".to_string())?;
+
+        let too_close = self
+            .points
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != slot)
+            .filter_map(|(_, p)| *p)
+            .find(|p| (to_resistance(p.reading as f64) - resistance).abs() < MIN_POINT_GAP);
+        if let Some(p) = too_close {
+            return Err(format!(
+                "The probe reads almost the same as it did for {:.1}C, calibrate further apart",
+                p.reference
+            ));
+        }
+
+        self.points[slot] = Some(point);
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.points = [None; 3];
+    }
+
+    // Map the resistance seen through the front-end onto the true resistance using the
+    // calibration points. One point corrects the offset, two a linear error and three
+    // fit a quadratic through the points.
+    pub fn correct(&self, resistance: f64, to_resistance: impl Fn(f64) -> f64) -> f64 {
+        let points: Vec<(f64, f64)> = self
+            .points
+            .iter()
+            .flatten()
+            .map(|p| {
+                (
+                    to_resistance(p.reading as f64),
+                    degrees_to_resistance(p.reference as f64),
+                )
+            })
+            .collect();
+
+        match points.as_slice() {
+            [] => resistance,
+            [(measured, actual)] => resistance + (actual - measured),
+            points => points.iter().enumerate().fold(0.0, |acc, (i, (xi, yi))| {
+                let basis = points
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .fold(1.0, |basis, (_, (xj, _))| {
+                        basis * (resistance - xj) / (xi - xj)
+                    });
+                acc + yi * basis
+            }),
+        }
+    }
+}
+
+pub fn degrees_to_resistance(degrees: f64) -> f64 {
+    let t = degrees;
+    if t >= 0.0 {
+        R0 * (1.0 + A * t + B * t * t)
+    } else {
+        R0 * (1.0 + A * t + B * t * t + C * (t - 100.0) * t * t * t)
+    }
+}
+
+pub fn resistance_to_degrees(resistance: f64) -> Result<f32, String> {
+    // Exact inverse above 0°C
+    let mut t = (-A + (A * A - 4.0 * B * (1.0 - resistance / R0)).sqrt()) / (2.0 * B);
+
+    // Below 0°C the C term makes it a quartic, refine with Newton's method
+    if resistance < R0 {
+        for _ in 0..NEWTON_ITERATIONS {
+            let f = degrees_to_resistance(t) - resistance;
+            let df = R0 * (A + 2.0 * B * t + C * (4.0 * t * t * t - 300.0 * t * t));
+            t -= f / df;
+        }
+    }
+
+    if !t.is_finite() || !(MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(&t) {
+        log::error!("Resistance out of range: {}", resistance);
+        return Err("Resistance out of range".to_string());
+    }
+
+    Ok(t as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A front end that reads ohms directly
+    fn ohms(reading: f64) -> f64 {
+        reading
+    }
+
+    fn point(reading: f64, reference: Temperature) -> CalibrationPoint {
+        CalibrationPoint {
+            reading: reading as f32,
+            reference,
+        }
+    }
+
+    #[test]
+    fn iec_60751_table() {
+        for (degrees, resistance) in [
+            (-190.0, 22.83),
+            (-100.0, 60.26),
+            (0.0, 100.0),
+            (100.0, 138.51),
+            (400.0, 247.09),
+            (850.0, 390.48),
+        ] {
+            assert!(
+                (degrees_to_resistance(degrees) - resistance).abs() < 0.01,
+                "{degrees}C"
+            );
+            let back = resistance_to_degrees(resistance).unwrap();
+            assert!(
+                (back as f64 - degrees).abs() < 0.05,
+                "{resistance} ohms gave {back}C"
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_round_trips_the_whole_range() {
+        for step in 0..=1050 {
+            let degrees = MIN_TEMPERATURE + step as f64;
+            let back = resistance_to_degrees(degrees_to_resistance(degrees)).unwrap();
+            assert!(
+                (back as f64 - degrees).abs() < 1e-3,
+                "{degrees}C came back as {back}C"
+            );
+        }
+    }
+
+    #[test]
+    fn inverse_rejects_impossible_resistances() {
+        assert!(resistance_to_degrees(0.0).is_err());
+        assert!(resistance_to_degrees(500.0).is_err());
+        assert!(resistance_to_degrees(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn one_point_corrects_offset() {
+        let mut calibration = Calibration::default();
+        let actual = degrees_to_resistance(93.0);
+        calibration
+            .add_point(point(actual + 0.8, 93.0), ohms)
+            .unwrap();
+        let corrected = calibration.correct(degrees_to_resistance(20.0) + 0.8, ohms);
+        assert!((corrected - degrees_to_resistance(20.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn points_are_fitted_exactly() {
+        // A front end that reads 2% high with a 1.5 ohm offset
+        let measured = |degrees: f64| degrees_to_resistance(degrees) * 1.02 + 1.5;
+        let mut calibration = Calibration::default();
+        for degrees in [20.0, 60.0, 100.0] {
+            calibration
+                .add_point(point(measured(degrees), degrees as f32), ohms)
+                .unwrap();
+        }
+        for degrees in [20.0, 45.0, 60.0, 93.0, 100.0] {
+            let corrected = calibration.correct(measured(degrees), ohms);
+            let back = resistance_to_degrees(corrected).unwrap();
+            assert!(
+                (back as f64 - degrees).abs() < 0.01,
+                "{degrees}C read as {back}C"
+            );
+        }
+    }
+
+    #[test]
+    fn close_readings_are_rejected() {
+        let mut calibration = Calibration::default();
+        calibration.add_point(point(120.0, 50.0), ohms).unwrap();
+        assert!(calibration.add_point(point(121.0, 60.0), ohms).is_err());
+        assert!(calibration.add_point(point(120.0, 90.0), ohms).is_err());
+        assert_eq!(calibration.points.iter().flatten().count(), 1);
+
+        calibration.add_point(point(135.0, 90.0), ohms).unwrap();
+        let corrected = calibration.correct(128.0, ohms);
+        assert!(corrected.is_finite());
+    }
+
+    #[test]
+    fn non_finite_points_are_rejected() {
+        let mut calibration = Calibration::default();
+        assert!(calibration.add_point(point(f64::NAN, 50.0), ohms).is_err());
+        assert!(calibration
+            .add_point(point(120.0, f32::INFINITY), ohms)
+            .is_err());
+        assert!(calibration
+            .add_point(point(120.0, 50.0), |_| f64::INFINITY)
+            .is_err());
+        assert_eq!(calibration, Calibration::default());
+    }
+
+    #[test]
+    fn nearby_reference_replaces_the_point() {
+        let mut calibration = Calibration::default();
+        calibration.add_point(point(120.0, 50.0), ohms).unwrap();
+        // Close to the old reading, but it replaces that point so there's nothing to divide by
+        calibration.add_point(point(121.0, 52.0), ohms).unwrap();
+        assert_eq!(calibration.points[0], Some(point(121.0, 52.0)));
+        assert_eq!(calibration.points[1], None);
+    }
+
+    #[test]
+    fn at_most_three_points() {
+        let mut calibration = Calibration::default();
+        for (reading, reference) in [(110.0, 25.0), (120.0, 50.0), (130.0, 75.0)] {
+            calibration
+                .add_point(point(reading, reference), ohms)
+                .unwrap();
+        }
+        assert!(calibration.add_point(point(140.0, 100.0), ohms).is_err());
+        calibration.clear();
+        assert_eq!(calibration, Calibration::default());
+    }
+}
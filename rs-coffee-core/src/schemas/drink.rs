@@ -0,0 +1,24 @@
+use super::{postinfusion::PostInfusion, preinfusion::PreInfusion, shot::Shot, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub type Menu = BTreeMap<u32, String>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Drink {
+    pub name: Option<String>,
+    pub preinfusion: Option<PreInfusion>,
+    pub shot: Shot,
+    pub postinfusion: Option<PostInfusion>,
+}
+
+impl Drink {
+    pub fn validate(&self) -> Result<(), Error> {
+        self.shot.validate()?;
+        if let Some(preinfusion) = &self.preinfusion {
+            preinfusion.validate()?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use super::{
+    drink, output, postinfusion, pressure, temperature, Converted, Output, Step as Segment,
+    DEFAULT_PRESSURE, DEFAULT_TEMPERATURE, NOMINAL_FLOW,
+};
+use crate::schemas::{drink::Drink, preinfusion::PreInfusion};
+use serde::{Deserialize, Serialize};
+
+// The DE1 app writes every number as a string, other tools that use the format write plain numbers
+mod text {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number {
+        Text(String),
+        Value(f32),
+    }
+
+    pub fn serialize<S: Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:.2}", value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        match Number::deserialize(deserializer)? {
+            Number::Value(value) => Ok(value),
+            Number::Text(text) if text.trim().is_empty() => Ok(0.0),
+            Number::Text(text) => text.trim().parse().map_err(D::Error::custom),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Pump {
+    Pressure,
+    Flow,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    #[default]
+    Fast,
+    Smooth,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Exit {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub condition: String,
+    #[serde(with = "text")]
+    pub value: f32,
+}
+
+// Caps pressure on a flow step, or flow on a pressure step
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Limiter {
+    #[serde(with = "text")]
+    pub value: f32,
+    #[serde(with = "text", default)]
+    pub range: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Step {
+    pub name: String,
+    #[serde(with = "text")]
+    pub temperature: f32,
+    #[serde(default)]
+    pub sensor: String,
+    pub pump: Pump,
+    #[serde(default)]
+    pub transition: Transition,
+    #[serde(with = "text", default)]
+    pub pressure: f32,
+    #[serde(with = "text", default)]
+    pub flow: f32,
+    #[serde(with = "text")]
+    pub seconds: f32,
+    #[serde(with = "text", default)]
+    pub volume: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit: Option<Exit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limiter: Option<Limiter>,
+}
+
+// Only the fields we use, the rest of a DE1 file is ignored on import
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub title: String,
+    #[serde(default)]
+    pub author: String,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub beverage_type: String,
+    pub steps: Vec<Step>,
+    #[serde(with = "text", default)]
+    pub target_weight: f32,
+    #[serde(with = "text", default)]
+    pub target_volume: f32,
+    #[serde(with = "text", default)]
+    pub tank_temperature: f32,
+    #[serde(default)]
+    pub legacy_profile_type: String,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+fn step_pressure(step: &Step, warnings: &mut Vec<String>) -> f32 {
+    match step.pump {
+        Pump::Pressure => {
+            if matches!(step.limiter, Some(limiter) if limiter.value > 0.0) {
+                warnings.push(format!("{}: flow limit ignored", step.name));
+            }
+            step.pressure
+        }
+        Pump::Flow => {
+            let limit = step
+                .limiter
+                .map(|limiter| limiter.value)
+                .filter(|value| *value > 0.0)
+                .unwrap_or(DEFAULT_PRESSURE);
+            warnings.push(format!(
+                "{}: flow control at {:.1} ml/s isn't supported, holding {:.1} bar instead",
+                step.name, step.flow, limit
+            ));
+            limit
+        }
+    }
+}
+
+// Things a step can do that a segment can't
+fn step_warnings(step: &Step, warnings: &mut Vec<String>) {
+    if step.transition == Transition::Smooth {
+        warnings.push(format!(
+            "{}: smooth transition replaced with a step change",
+            step.name
+        ));
+    }
+    if let Some(exit) = &step.exit {
+        warnings.push(format!(
+            "{}: exit on {} {} {:.1} ignored, the step runs for its full time",
+            step.name, exit.kind, exit.condition, exit.value
+        ));
+    }
+    if step.volume > 0.0 {
+        warnings.push(format!("{}: volume limit ignored", step.name));
+    }
+}
+
+pub fn import(profile: &Profile) -> anyhow::Result<Converted<Drink>> {
+    let mut warnings = vec![];
+    if profile.tank_temperature > 0.0 {
+        warnings.push("Tank preheating isn't supported".to_string());
+    }
+
+    // A leading preinfusion step becomes our preinfusion, which is how we export it too
+    let (preinfusion, shot_steps) = match profile.steps.split_first() {
+        Some((first, rest)) if !rest.is_empty() && first.name.to_lowercase().contains("infus") => {
+            step_warnings(first, &mut warnings);
+            let step_pressure = step_pressure(first, &mut warnings);
+            let preinfusion = PreInfusion {
+                time: first.seconds,
+                pressure: pressure(&first.name, step_pressure, &mut warnings),
+            };
+            (Some(preinfusion), rest)
+        }
+        _ => (None, &profile.steps[..]),
+    };
+
+    let mut steps = vec![];
+    for step in shot_steps {
+        if step.seconds <= 0.0 {
+            warnings.push(format!("{}: no duration, dropped", step.name));
+            continue;
+        }
+        step_warnings(step, &mut warnings);
+        let step_pressure = step_pressure(step, &mut warnings);
+        steps.push(Segment {
+            length: step.seconds,
+            pressure: pressure(&step.name, step_pressure, &mut warnings),
+            degrees: temperature(&step.name, step.temperature, &mut warnings),
+        });
+    }
+
+    let output = if profile.target_weight > 0.0 {
+        Output::Weight(profile.target_weight)
+    } else {
+        if profile.target_volume > 0.0 {
+            warnings.push("Volume target isn't supported, the shot is timed instead".to_string());
+        }
+        Output::Time(steps.iter().map(|step| step.length).sum())
+    };
+
+    Ok(Converted {
+        value: drink(profile.title.clone(), preinfusion, output, steps)?,
+        warnings,
+    })
+}
+
+fn step(name: String, degrees: f32, bar: f32, seconds: f32) -> Step {
+    Step {
+        name,
+        temperature: degrees,
+        sensor: "coffee".to_string(),
+        pump: Pump::Pressure,
+        transition: Transition::Fast,
+        pressure: bar,
+        flow: 0.0,
+        seconds,
+        volume: 0.0,
+        exit: None,
+        limiter: None,
+    }
+}
+
+pub fn export(drink: &Drink) -> Converted<Profile> {
+    let mut warnings = vec![];
+    postinfusion(drink, &mut warnings);
+
+    let output = output(&drink.shot);
+    let total_seconds = match output {
+        Output::Weight(weight) => {
+            warnings.push(format!(
+                "Steps are shares of a {:.1}g yield, their times assume {:.1} g/s",
+                weight, NOMINAL_FLOW
+            ));
+            weight / NOMINAL_FLOW
+        }
+        Output::Time(time) => time,
+    };
+    let first_degrees = drink
+        .shot
+        .profile
+        .first()
+        .map(|segment| segment.degrees)
+        .unwrap_or(DEFAULT_TEMPERATURE);
+
+    let mut steps: Vec<Step> = drink
+        .preinfusion
+        .iter()
+        .map(|preinfusion| {
+            step(
+                "preinfusion".to_string(),
+                first_degrees,
+                preinfusion.pressure,
+                preinfusion.time,
+            )
+        })
+        .collect();
+    steps.extend(
+        drink
+            .shot
+            .profile
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                step(
+                    format!("segment {}", index + 1),
+                    segment.degrees,
+                    segment.pressure,
+                    total_seconds * segment.percentage as f32 / 100.0,
+                )
+            }),
+    );
+
+    Converted {
+        value: Profile {
+            title: drink.name.clone().unwrap_or_default(),
+            author: String::new(),
+            notes: String::new(),
+            beverage_type: "espresso".to_string(),
+            steps,
+            target_weight: match output {
+                Output::Weight(weight) => weight,
+                Output::Time(_) => 0.0,
+            },
+            target_volume: 0.0,
+            tank_temperature: 0.0,
+            legacy_profile_type: "settings_2c".to_string(),
+            kind: "advanced".to_string(),
+            version: "2".to_string(),
+        },
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::formats::tests::espresso;
+
+    // Trimmed from a profile saved by the DE1 app, which quotes every number
+    const BLOOMING: &str = r#"{
+        "title": "Blooming espresso",
+        "author": "Decent",
+        "beverage_type": "espresso",
+        "target_weight": "40",
+        "tank_temperature": "0",
+        "steps": [
+            {"name": "preinfusion", "temperature": "92.00", "sensor": "coffee", "pump": "flow",
+             "transition": "fast", "pressure": "1", "flow": "4.0", "seconds": "8.00",
+             "volume": "100", "exit": {"type": "pressure", "condition": "over", "value": "4.00"},
+             "limiter": {"value": "4", "range": "0.6"}},
+            {"name": "bloom", "temperature": "92.00", "pump": "pressure", "pressure": "0",
+             "seconds": "0.00"},
+            {"name": "rise", "temperature": "92.00", "pump": "pressure", "transition": "smooth",
+             "pressure": "9.00", "seconds": 10},
+            {"name": "decline", "temperature": "90.00", "pump": "pressure",
+             "pressure": "6.0", "seconds": "30"}
+        ]
+    }"#;
+
+    #[test]
+    fn import_app_profile() {
+        let converted = import(&serde_json::from_str(BLOOMING).unwrap()).unwrap();
+        let drink = converted.value;
+        assert_eq!(drink.name.as_deref(), Some("Blooming espresso"));
+        assert_eq!(
+            drink.preinfusion,
+            Some(PreInfusion {
+                time: 8.0,
+                pressure: 4.0
+            })
+        );
+        assert_eq!(drink.shot.weight, Some(40.0));
+        assert_eq!(drink.shot.time, None);
+        let profile: Vec<_> = drink
+            .shot
+            .profile
+            .iter()
+            .map(|segment| (segment.degrees, segment.pressure, segment.percentage))
+            .collect();
+        assert_eq!(profile, vec![(92.0, 9.0, 25), (90.0, 6.0, 75)]);
+
+        let warnings = converted.warnings.join("\n");
+        for expected in [
+            "preinfusion: flow control",
+            "preinfusion: exit on pressure over 4.0",
+            "preinfusion: volume limit",
+            "bloom: no duration",
+            "rise: smooth transition",
+        ] {
+            assert!(
+                warnings.contains(expected),
+                "{expected} missing from\n{warnings}"
+            );
+        }
+    }
+
+    #[test]
+    fn export_times_a_weighed_shot_by_nominal_flow() {
+        let converted = export(&espresso());
+        let profile = converted.value;
+        assert_eq!(profile.target_weight, 36.0);
+        let seconds: Vec<f32> = profile.steps.iter().map(|step| step.seconds).collect();
+        assert_eq!(seconds, vec![4.0, 7.2, 10.8, 6.0]);
+        assert_eq!(converted.warnings.len(), 1);
+
+        let json = serde_json::to_value(&profile).unwrap();
+        assert_eq!(json["steps"][1]["pressure"], "9.00");
+    }
+
+    #[test]
+    fn timed_profile_keeps_its_time() {
+        let json = r#"{"title": "Turbo", "steps": [
+            {"name": "fill", "temperature": 94, "pump": "pressure", "pressure": 6, "seconds": 5},
+            {"name": "pour", "temperature": 94, "pump": "pressure", "pressure": 6, "seconds": 15}
+        ]}"#;
+        let drink = import(&serde_json::from_str(json).unwrap()).unwrap().value;
+        assert_eq!(drink.preinfusion, None);
+        assert_eq!(drink.shot.weight, None);
+        assert_eq!(drink.shot.time, Some(20.0));
+    }
+}
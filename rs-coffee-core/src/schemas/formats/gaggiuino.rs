@@ -0,0 +1,430 @@
+use super::{
+    drink, output, postinfusion, pressure, temperature, Converted, Output, Step as Segment,
+    DEFAULT_PRESSURE, DEFAULT_TEMPERATURE,
+};
+use crate::schemas::{drink::Drink, preinfusion::PreInfusion};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PhaseType {
+    Flow,
+    Pressure,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Curve {
+    #[default]
+    Instant,
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+// Times are in milliseconds throughout
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+pub struct Target {
+    #[serde(default)]
+    pub start: f32,
+    pub end: f32,
+    #[serde(default)]
+    pub curve: Curve,
+    #[serde(default)]
+    pub time: u32,
+}
+
+// The weight here is the total in the cup, not what came out during the phase
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StopConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pressure_above: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pressure_below: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_above: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flow_below: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub water_pumped_in_phase: Option<f32>,
+}
+
+impl StopConditions {
+    fn others(&self) -> bool {
+        self.pressure_above.is_some()
+            || self.pressure_below.is_some()
+            || self.flow_above.is_some()
+            || self.flow_below.is_some()
+            || self.water_pumped_in_phase.is_some()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Phase {
+    #[serde(rename = "type")]
+    pub kind: PhaseType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub target: Target,
+    // The flow cap on a pressure phase, or the pressure cap on a flow phase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restriction: Option<f32>,
+    #[serde(default)]
+    pub skip: bool,
+    #[serde(default)]
+    pub stop_conditions: StopConditions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub water_temperature: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalStopConditions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub water_pumped: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Recipe {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coffee_in: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coffee_out: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratio: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    pub phases: Vec<Phase>,
+    #[serde(default)]
+    pub global_stop_conditions: GlobalStopConditions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub water_temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<Recipe>,
+}
+
+fn phase_name(index: usize, phase: &Phase) -> String {
+    phase
+        .name
+        .clone()
+        .unwrap_or_else(|| format!("Phase {}", index + 1))
+}
+
+fn phase_pressure(name: &str, phase: &Phase, warnings: &mut Vec<String>) -> f32 {
+    match phase.kind {
+        PhaseType::Pressure => {
+            if phase.target.curve != Curve::Instant && phase.target.start != phase.target.end {
+                warnings.push(format!(
+                    "{}: {:?} ramp from {:.1} bar replaced with a step change",
+                    name, phase.target.curve, phase.target.start
+                ));
+            }
+            if matches!(phase.restriction, Some(restriction) if restriction > 0.0) {
+                warnings.push(format!("{}: flow restriction ignored", name));
+            }
+            phase.target.end
+        }
+        PhaseType::Flow => {
+            let limit = phase
+                .restriction
+                .filter(|restriction| *restriction > 0.0)
+                .unwrap_or(DEFAULT_PRESSURE);
+            warnings.push(format!(
+                "{}: flow control at {:.1} ml/s isn't supported, holding {:.1} bar instead",
+                name, phase.target.end, limit
+            ));
+            limit
+        }
+    }
+}
+
+pub fn import(profile: &Profile) -> anyhow::Result<Converted<Drink>> {
+    let mut warnings = vec![];
+    let global = profile.global_stop_conditions;
+    if global.water_pumped.is_some() {
+        warnings.push("Water pumped stop condition ignored".to_string());
+    }
+    let output = match (global.weight, profile.recipe.and_then(|r| r.coffee_out)) {
+        (Some(weight), _) | (None, Some(weight)) => Output::Weight(weight),
+        // Filled in once the phases have been added up
+        (None, None) => Output::Time(global.time.unwrap_or_default() as f32 / 1000.0),
+    };
+
+    let phases: Vec<(String, &Phase)> = profile
+        .phases
+        .iter()
+        .enumerate()
+        .map(|(index, phase)| (phase_name(index, phase), phase))
+        .filter(|(name, phase)| {
+            if phase.skip {
+                warnings.push(format!("{}: skipped", name));
+            }
+            !phase.skip
+        })
+        .collect();
+
+    // A timed phase ahead of weighed ones is how we export preinfusion, so it comes back as one
+    let (preinfusion, phases) = match phases.split_first() {
+        Some(((name, first), rest))
+            if matches!(output, Output::Weight(_))
+                && first.stop_conditions.weight.is_none()
+                && first.stop_conditions.time.is_some()
+                && !rest.is_empty() =>
+        {
+            let phase_pressure = phase_pressure(name, first, &mut warnings);
+            let preinfusion = PreInfusion {
+                time: first.stop_conditions.time.unwrap_or_default() as f32 / 1000.0,
+                pressure: pressure(name, phase_pressure, &mut warnings),
+            };
+            (Some(preinfusion), rest)
+        }
+        _ => (None, &phases[..]),
+    };
+
+    // Weight stops divide a weighed shot exactly, otherwise fall back to the phase times
+    let by_weight = matches!(output, Output::Weight(_))
+        && phases
+            .iter()
+            .all(|(_, phase)| phase.stop_conditions.weight.is_some());
+
+    let mut steps = vec![];
+    let mut previous_weight = 0.0;
+    for (name, phase) in phases {
+        let stop = phase.stop_conditions;
+        let length = if by_weight {
+            let weight = stop.weight.unwrap_or_default();
+            let length = weight - previous_weight;
+            previous_weight = weight;
+            length
+        } else {
+            stop.time.unwrap_or_default() as f32 / 1000.0
+        };
+        if length <= 0.0 {
+            warnings.push(format!("{}: no duration, dropped", name));
+            continue;
+        }
+        if stop.others() || (!by_weight && stop.weight.is_some()) {
+            warnings.push(format!(
+                "{}: stop conditions other than {} ignored",
+                name,
+                if by_weight { "weight" } else { "time" }
+            ));
+        }
+
+        let phase_pressure = phase_pressure(name, phase, &mut warnings);
+        let degrees = phase
+            .water_temperature
+            .or(profile.water_temperature)
+            .unwrap_or(DEFAULT_TEMPERATURE);
+        steps.push(Segment {
+            length,
+            pressure: pressure(name, phase_pressure, &mut warnings),
+            degrees: temperature(name, degrees, &mut warnings),
+        });
+    }
+
+    let output = match output {
+        Output::Time(time) if time <= 0.0 => {
+            Output::Time(steps.iter().map(|step| step.length).sum())
+        }
+        output => output,
+    };
+
+    Ok(Converted {
+        value: drink(profile.name.clone(), preinfusion, output, steps)?,
+        warnings,
+    })
+}
+
+fn phase(bar: f32, degrees: Option<f32>, stop_conditions: StopConditions) -> Phase {
+    Phase {
+        kind: PhaseType::Pressure,
+        name: None,
+        target: Target {
+            start: bar,
+            end: bar,
+            curve: Curve::Instant,
+            time: 0,
+        },
+        restriction: None,
+        skip: false,
+        stop_conditions,
+        water_temperature: degrees,
+    }
+}
+
+pub fn export(drink: &Drink) -> Converted<Profile> {
+    let mut warnings = vec![];
+    postinfusion(drink, &mut warnings);
+
+    let output = output(&drink.shot);
+    let water_temperature = drink
+        .shot
+        .profile
+        .first()
+        .map(|segment| segment.degrees)
+        .unwrap_or(DEFAULT_TEMPERATURE);
+    // Only phases that differ from the profile's temperature carry their own
+    let degrees = |degrees: f32| (degrees != water_temperature).then_some(degrees);
+
+    let mut phases: Vec<Phase> = drink
+        .preinfusion
+        .iter()
+        .map(|preinfusion| {
+            phase(
+                preinfusion.pressure,
+                None,
+                StopConditions {
+                    time: Some((preinfusion.time * 1000.0) as u32),
+                    ..Default::default()
+                },
+            )
+        })
+        .collect();
+    let mut share = 0.0;
+    for segment in &drink.shot.profile {
+        share += segment.percentage as f32 / 100.0;
+        let stop_conditions = match output {
+            Output::Weight(weight) => StopConditions {
+                weight: Some((weight * share * 10.0).round() / 10.0),
+                ..Default::default()
+            },
+            Output::Time(time) => StopConditions {
+                time: Some((time * segment.percentage as f32 / 100.0 * 1000.0) as u32),
+                ..Default::default()
+            },
+        };
+        phases.push(phase(
+            segment.pressure,
+            degrees(segment.degrees),
+            stop_conditions,
+        ));
+    }
+
+    let global_stop_conditions = match output {
+        Output::Weight(weight) => GlobalStopConditions {
+            weight: Some(weight),
+            ..Default::default()
+        },
+        Output::Time(time) => GlobalStopConditions {
+            time: Some((time * 1000.0) as u32),
+            ..Default::default()
+        },
+    };
+
+    Converted {
+        value: Profile {
+            name: drink.name.clone().unwrap_or_default(),
+            phases,
+            global_stop_conditions,
+            water_temperature: Some(water_temperature),
+            recipe: None,
+        },
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::formats::{tests::espresso, Format};
+
+    const LEVER: &str = r#"{
+        "name": "Londinium",
+        "phases": [
+            {"type": "FLOW", "name": "Fill", "target": {"end": 6}, "restriction": 2,
+             "stopConditions": {"time": 6000, "pressureAbove": 2}},
+            {"type": "PRESSURE", "target": {"start": 2, "end": 2, "time": 0},
+             "stopConditions": {"time": 5000}, "skip": true},
+            {"type": "PRESSURE", "name": "Peak", "target": {"start": 9, "end": 9},
+             "stopConditions": {"weight": 12}},
+            {"type": "PRESSURE", "name": "Decline", "target": {"start": 9, "end": 5,
+             "curve": "EASE_OUT", "time": 20000}, "stopConditions": {"weight": 36},
+             "waterTemperature": 90}
+        ],
+        "globalStopConditions": {"weight": 36},
+        "waterTemperature": 93
+    }"#;
+
+    #[test]
+    fn import_lever_profile() {
+        let converted = import(&serde_json::from_str(LEVER).unwrap()).unwrap();
+        let drink = converted.value;
+        assert_eq!(
+            drink.preinfusion,
+            Some(PreInfusion {
+                time: 6.0,
+                pressure: 3.0
+            })
+        );
+        assert_eq!(drink.shot.weight, Some(36.0));
+        let profile: Vec<_> = drink
+            .shot
+            .profile
+            .iter()
+            .map(|segment| (segment.degrees, segment.pressure, segment.percentage))
+            .collect();
+        assert_eq!(profile, vec![(93.0, 9.0, 33), (90.0, 5.0, 67)]);
+
+        let warnings = converted.warnings.join("\n");
+        for expected in [
+            "Phase 2: skipped",
+            "Fill: flow control at 6.0 ml/s",
+            "Fill: 2.0 bar is out of range",
+            "Decline: EaseOut ramp",
+        ] {
+            assert!(
+                warnings.contains(expected),
+                "{expected} missing from\n{warnings}"
+            );
+        }
+    }
+
+    #[test]
+    fn export_writes_cumulative_weights() {
+        let converted = export(&espresso());
+        let profile = converted.value;
+        assert!(converted.warnings.is_empty());
+        let weights: Vec<_> = profile
+            .phases
+            .iter()
+            .map(|phase| phase.stop_conditions.weight)
+            .collect();
+        assert_eq!(weights, vec![None, Some(10.8), Some(27.0), Some(36.0)]);
+        assert_eq!(profile.phases[0].stop_conditions.time, Some(4000));
+        assert_eq!(profile.phases[3].water_temperature, Some(91.0));
+        assert_eq!(profile.phases[1].water_temperature, None);
+        assert_eq!(profile.global_stop_conditions.weight, Some(36.0));
+
+        let json = Format::Gaggiuino.export(&espresso()).unwrap().value;
+        assert_eq!(json["phases"][1]["type"], "PRESSURE");
+        assert_eq!(json["phases"][1]["stopConditions"]["weight"], 10.8);
+    }
+
+    #[test]
+    fn timed_phases_without_a_yield() {
+        let json = r#"{"name": "Timed", "phases": [
+            {"type": "PRESSURE", "target": {"end": 9}, "stopConditions": {"time": 25000}}
+        ]}"#;
+        let drink = import(&serde_json::from_str(json).unwrap()).unwrap().value;
+        assert_eq!(drink.preinfusion, None);
+        assert_eq!(drink.shot.time, Some(25.0));
+        assert_eq!(drink.shot.profile[0].percentage, 100);
+    }
+}
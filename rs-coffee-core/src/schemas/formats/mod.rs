@@ -0,0 +1,259 @@
+use super::{
+    drink::Drink, postinfusion::PostInfusion, preinfusion::PreInfusion, shot::Profile as Segment,
+    shot::Shot,
+};
+use crate::config::Shots as config;
+use crate::types::{Bar, Degrees, Grams};
+use serde::Serialize;
+use std::str::FromStr;
+
+pub mod de1;
+pub mod gaggiuino;
+
+// Conversions are plain data in, data out with no I/O, so they can be exercised on the host
+
+// Flow controlled steps have no pressure of their own, this is used when they don't set a limit
+const DEFAULT_PRESSURE: Bar = 9.0;
+const DEFAULT_TEMPERATURE: Degrees = 94.0;
+// Used to put a duration on segments of a shot that's stopped by weight
+const NOMINAL_FLOW: f32 = 1.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format {
+    De1,
+    Gaggiuino,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "de1" | "decent" => Ok(Format::De1),
+            "gaggiuino" => Ok(Format::Gaggiuino),
+            _ => Err(anyhow::anyhow!("Unknown profile format {}", s)),
+        }
+    }
+}
+
+// Anything the other side can't express is approximated or dropped, and listed in the warnings
+#[derive(Serialize, Debug, Clone)]
+pub struct Converted<T> {
+    pub warnings: Vec<String>,
+    #[serde(rename = "profile")]
+    pub value: T,
+}
+
+impl Format {
+    pub fn import(&self, json: &str) -> anyhow::Result<Converted<Drink>> {
+        let converted = match self {
+            Format::De1 => de1::import(&serde_json::from_str(json)?)?,
+            Format::Gaggiuino => gaggiuino::import(&serde_json::from_str(json)?)?,
+        };
+        converted.value.validate()?;
+        Ok(converted)
+    }
+
+    pub fn export(&self, drink: &Drink) -> anyhow::Result<Converted<serde_json::Value>> {
+        let (json, warnings) = match self {
+            Format::De1 => {
+                let converted = de1::export(drink);
+                (serde_json::to_string(&converted.value)?, converted.warnings)
+            }
+            Format::Gaggiuino => {
+                let converted = gaggiuino::export(drink);
+                (serde_json::to_string(&converted.value)?, converted.warnings)
+            }
+        };
+        // Going through text keeps 10.8 as 10.8, `to_value` would widen the f32 to 10.800000190734863
+        let value = serde_json::from_str(&json)?;
+        Ok(Converted { warnings, value })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Output {
+    Weight(Grams),
+    Time(f32),
+}
+
+// A step from another format, reduced to what one of our segments can hold
+struct Step {
+    length: f32,
+    pressure: Bar,
+    degrees: Degrees,
+}
+
+fn pressure(step: &str, pressure: Bar, warnings: &mut Vec<String>) -> Bar {
+    let clamped = pressure.clamp(config::MIN_SHOT_PRESSURE_BAR, config::MAX_SHOT_PRESSURE_BAR);
+    if clamped != pressure {
+        warnings.push(format!(
+            "{}: {:.1} bar is out of range, using {:.1} bar",
+            step, pressure, clamped
+        ));
+    }
+    clamped
+}
+
+fn temperature(step: &str, degrees: Degrees, warnings: &mut Vec<String>) -> Degrees {
+    let clamped = degrees.clamp(config::MIN_SHOT_TEMPERATURE, config::MAX_SHOT_TEMPERATURE);
+    if clamped != degrees {
+        warnings.push(format!(
+            "{}: {:.1}C is out of range, using {:.1}C",
+            step, degrees, clamped
+        ));
+    }
+    clamped
+}
+
+// Whole percentages that always add up to 100, the rounding going to the steps that lost the most
+fn percentages(lengths: &[f32]) -> Vec<u8> {
+    let total: f32 = lengths.iter().sum();
+    let exact: Vec<f32> = lengths
+        .iter()
+        .map(|length| length / total * 100.0)
+        .collect();
+    let mut shares: Vec<u8> = exact.iter().map(|share| share.floor() as u8).collect();
+    let missing = 100u32.saturating_sub(shares.iter().map(|&share| share as u32).sum());
+
+    let mut order: Vec<usize> = (0..exact.len()).collect();
+    let remainder = |index: usize| exact[index] - exact[index].floor();
+    order.sort_by(|&a, &b| remainder(b).total_cmp(&remainder(a)));
+    for &index in order.iter().cycle().take(missing as usize) {
+        shares[index] += 1;
+    }
+    shares
+}
+
+fn drink(
+    name: String,
+    preinfusion: Option<PreInfusion>,
+    output: Output,
+    steps: Vec<Step>,
+) -> anyhow::Result<Drink> {
+    if name.trim().is_empty() {
+        return Err(anyhow::anyhow!("Profile has no name"));
+    }
+    if steps.is_empty() {
+        return Err(anyhow::anyhow!("Profile has no usable steps"));
+    }
+    let lengths: Vec<f32> = steps.iter().map(|step| step.length).collect();
+    let profile = steps
+        .iter()
+        .zip(percentages(&lengths))
+        .map(|(step, percentage)| Segment::new(step.degrees, step.pressure, percentage))
+        .collect();
+    let (weight, time) = match output {
+        Output::Weight(weight) => (Some(weight), None),
+        Output::Time(time) => (None, Some(time)),
+    };
+    Ok(Drink {
+        name: Some(name),
+        preinfusion,
+        shot: Shot {
+            weight,
+            time,
+            profile,
+        },
+        postinfusion: None,
+    })
+}
+
+fn output(shot: &Shot) -> Output {
+    match (shot.weight, shot.time) {
+        (Some(weight), _) => Output::Weight(weight),
+        (None, time) => Output::Time(time.unwrap_or_default()),
+    }
+}
+
+fn postinfusion(drink: &Drink, warnings: &mut Vec<String>) {
+    if matches!(&drink.postinfusion, Some(postinfusion) if *postinfusion != PostInfusion::Idle) {
+        warnings.push("Post infusion heating isn't supported, it was left out".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::shot::ShotBuilder;
+
+    pub(super) fn espresso() -> Drink {
+        Drink {
+            name: Some("Espresso".to_string()),
+            preinfusion: Some(PreInfusion {
+                time: 4.0,
+                pressure: 3.0,
+            }),
+            shot: ShotBuilder::new()
+                .by_weight(36.0)
+                .add_profile(Segment::new(93.0, 9.0, 30))
+                .add_profile(Segment::new(93.0, 7.5, 45))
+                .add_profile(Segment::new(91.0, 6.0, 25))
+                .build()
+                .unwrap(),
+            postinfusion: None,
+        }
+    }
+
+    #[test]
+    fn percentages_add_up_to_100() {
+        for lengths in [
+            vec![1.0],
+            vec![1.0, 1.0, 1.0],
+            vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0],
+            vec![0.1, 0.1, 99.8],
+            vec![12.5, 7.3, 30.1],
+        ] {
+            let shares = percentages(&lengths);
+            assert_eq!(shares.len(), lengths.len());
+            assert_eq!(
+                shares.iter().map(|&s| s as u32).sum::<u32>(),
+                100,
+                "{lengths:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn percentages_keep_exact_shares() {
+        assert_eq!(percentages(&[1.0, 3.0]), vec![25, 75]);
+        assert_eq!(percentages(&[9.0, 13.5, 7.5]), vec![30, 45, 25]);
+    }
+
+    #[test]
+    fn percentages_round_up_the_largest_remainders() {
+        assert_eq!(percentages(&[1.0, 1.0, 1.0]), vec![34, 33, 33]);
+        assert_eq!(percentages(&[1.0, 2.0, 2.0, 2.0]), vec![14, 29, 29, 28]);
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!("DE1".parse::<Format>().unwrap(), Format::De1);
+        assert_eq!("decent".parse::<Format>().unwrap(), Format::De1);
+        assert_eq!("Gaggiuino".parse::<Format>().unwrap(), Format::Gaggiuino);
+        assert!("lelit".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn exported_profiles_import_unchanged() {
+        let drink = espresso();
+        for format in [Format::De1, Format::Gaggiuino] {
+            let exported = format.export(&drink).unwrap();
+            let json = exported.value.to_string();
+            let imported = format.import(&json).unwrap();
+            assert_eq!(imported.value, drink, "{format:?}");
+            assert!(
+                imported.warnings.is_empty(),
+                "{format:?}: {:?}",
+                imported.warnings
+            );
+        }
+    }
+
+    #[test]
+    fn imports_are_validated() {
+        let json = r#"{"name": "", "phases": []}"#;
+        assert!(Format::Gaggiuino.import(json).is_err());
+        assert!(Format::De1.import("not json").is_err());
+    }
+}
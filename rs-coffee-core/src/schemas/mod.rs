@@ -0,0 +1,8 @@
+pub mod drink;
+mod error;
+pub mod formats;
+pub mod postinfusion;
+pub mod preinfusion;
+pub mod shot;
+
+pub use error::Error;
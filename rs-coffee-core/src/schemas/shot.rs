@@ -121,6 +121,12 @@ impl Profile {
     }
 }
 
+impl Default for ShotBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ShotBuilder {
     pub fn new() -> Self {
         ShotBuilder {
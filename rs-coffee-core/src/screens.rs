@@ -0,0 +1,407 @@
+use crate::types::{Bar, Grams, Temperature};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle},
+    text::{Baseline, Text},
+};
+
+// Rendering only ever sees a `DrawTarget`, so the same screens can be drawn into an
+// in-memory framebuffer on the host
+
+const LINE_HEIGHT: i32 = 11;
+const GRAPH_TOP: i32 = LINE_HEIGHT + 1;
+const GRAPH_MAX_PRESSURE: Bar = 12.0;
+const GRAPH_MIN_WEIGHT: Grams = 40.0;
+const MAX_SAMPLES: usize = 128;
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Screen {
+    #[default]
+    Live,
+    Shot,
+    Drink,
+    Alerts,
+}
+
+impl Screen {
+    const ALL: [Screen; 4] = [Screen::Live, Screen::Shot, Screen::Drink, Screen::Alerts];
+
+    fn index(&self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|screen| screen == self)
+            .unwrap_or(0)
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(&self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Screen::Live => "Live",
+            Screen::Shot => "Shot",
+            Screen::Drink => "Drink",
+            Screen::Alerts => "Alerts",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub pressure: Bar,
+    pub weight: Grams,
+}
+
+// Everything the screens show, None where the sensor has no trustworthy reading
+#[derive(Debug, Clone, Default)]
+pub struct Model {
+    pub temperature: Option<Temperature>,
+    pub pressure: Option<Bar>,
+    pub weight: Option<Grams>,
+    pub shot: Vec<Sample>,
+    pub drink: Option<String>,
+    pub alert: Option<String>,
+}
+
+impl Model {
+    pub fn record(&mut self, sample: Sample) {
+        if self.shot.len() == MAX_SAMPLES {
+            self.shot.remove(0);
+        }
+        self.shot.push(sample);
+    }
+}
+
+fn text<D>(target: &mut D, line: i32, content: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::with_baseline(
+        content,
+        Point::new(0, line * LINE_HEIGHT),
+        style,
+        Baseline::Top,
+    )
+    .draw(target)?;
+    Ok(())
+}
+
+fn reading<T: std::fmt::Display>(value: Option<T>, unit: &str) -> String {
+    match value {
+        Some(value) => format!("{:.1} {}", value, unit),
+        None => "--".to_string(),
+    }
+}
+
+// Wraps on spaces so a long SystemState message still fits across a few lines
+fn wrap(message: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in message.split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() + 1 > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn live<D>(model: &Model, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    text(
+        target,
+        1,
+        &format!("Boiler   {}", reading(model.temperature, "C")),
+    )?;
+    text(
+        target,
+        2,
+        &format!("Pressure {}", reading(model.pressure, "bar")),
+    )?;
+    text(
+        target,
+        3,
+        &format!("Weight   {}", reading(model.weight, "g")),
+    )?;
+    if model.alert.is_some() {
+        text(target, 5, "! See alerts")?;
+    }
+    Ok(())
+}
+
+// Pressure is drawn against a fixed scale, the weight scale grows to fit the shot
+fn shot<D>(model: &Model, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let Some(last) = model.shot.last() else {
+        return text(target, 2, "No shot yet");
+    };
+    text(
+        target,
+        0,
+        &format!("{:>9.1}g {:.1}bar", last.weight, last.pressure),
+    )?;
+
+    let size = target.bounding_box().size;
+    let height = size.height as i32 - GRAPH_TOP;
+    let max_weight = model
+        .shot
+        .iter()
+        .map(|sample| sample.weight)
+        .fold(GRAPH_MIN_WEIGHT, f32::max);
+    let y = |value: f32, max: f32| {
+        let fraction = (value / max).clamp(0.0, 1.0);
+        size.height as i32 - 1 - (fraction * (height - 1) as f32) as i32
+    };
+
+    let line = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    for (x, pair) in model.shot.windows(2).enumerate() {
+        let x = x as i32;
+        Line::new(
+            Point::new(x, y(pair[0].pressure, GRAPH_MAX_PRESSURE)),
+            Point::new(x + 1, y(pair[1].pressure, GRAPH_MAX_PRESSURE)),
+        )
+        .into_styled(line)
+        .draw(target)?;
+        // Dotted so the two traces can be told apart on a monochrome panel
+        if x % 2 == 0 {
+            Pixel(
+                Point::new(x, y(pair[0].weight, max_weight)),
+                BinaryColor::On,
+            )
+            .draw(target)?;
+        }
+    }
+    Ok(())
+}
+
+fn drink<D>(model: &Model, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    match &model.drink {
+        Some(name) => {
+            for (index, line) in wrap(name, 21).iter().take(4).enumerate() {
+                text(target, 2 + index as i32, line)?;
+            }
+            Ok(())
+        }
+        None => text(target, 2, "None selected"),
+    }
+}
+
+fn alerts<D>(model: &Model, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    match &model.alert {
+        Some(alert) => {
+            for (index, line) in wrap(alert, 21).iter().take(5).enumerate() {
+                text(target, 1 + index as i32, line)?;
+            }
+            Ok(())
+        }
+        None => text(target, 2, "All good"),
+    }
+}
+
+pub fn draw<D>(screen: Screen, model: &Model, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    target.clear(BinaryColor::Off)?;
+    text(target, 0, screen.title())?;
+    match screen {
+        Screen::Live => live(model, target),
+        Screen::Shot => shot(model, target),
+        Screen::Drink => drink(model, target),
+        Screen::Alerts => alerts(model, target),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::path::Path;
+
+    const WIDTH: usize = 128;
+    const HEIGHT: usize = 64;
+
+    // Stands in for the 128x64 SSD1306
+    struct Framebuffer([[bool; WIDTH]; HEIGHT]);
+
+    impl DrawTarget for Framebuffer {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                if let (Ok(x), Ok(y)) = (usize::try_from(point.x), usize::try_from(point.y)) {
+                    if x < WIDTH && y < HEIGHT {
+                        self.0[y][x] = color.is_on();
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl OriginDimensions for Framebuffer {
+        fn size(&self) -> Size {
+            Size::new(WIDTH as u32, HEIGHT as u32)
+        }
+    }
+
+    fn render(screen: Screen, model: &Model) -> String {
+        let mut framebuffer = Framebuffer([[false; WIDTH]; HEIGHT]);
+        draw(screen, model, &mut framebuffer).unwrap();
+        framebuffer
+            .0
+            .iter()
+            .map(|row| {
+                let mut line: String = row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+                line.push('\n');
+                line
+            })
+            .collect()
+    }
+
+    // Snapshots are kept as text, one character per pixel. Run with UPDATE_SNAPSHOTS=1 to
+    // write them again after changing a screen on purpose.
+    fn assert_snapshot(name: &str, rendered: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("snapshots")
+            .join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, rendered).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("No snapshot at {}", path.display()));
+        assert!(
+            expected == rendered,
+            "{} no longer matches its snapshot\nexpected:\n{}\nrendered:\n{}",
+            name,
+            expected,
+            rendered
+        );
+    }
+
+    fn brewing() -> Model {
+        let mut model = Model {
+            temperature: Some(93.4),
+            pressure: Some(8.8),
+            weight: Some(31.2),
+            drink: Some("Espresso".to_string()),
+            ..Default::default()
+        };
+        for tick in 0..100 {
+            let pressure = match tick {
+                0..=19 => tick as f32 * 0.45,
+                20..=69 => 9.0,
+                _ => 9.0 - (tick - 70) as f32 * 0.1,
+            };
+            let weight = if tick < 25 {
+                0.0
+            } else {
+                (tick - 25) as f32 * 0.42
+            };
+            model.record(Sample { pressure, weight });
+        }
+        model
+    }
+
+    #[test]
+    fn live() {
+        let mut model = brewing();
+        assert_snapshot("live", &render(Screen::Live, &model));
+        model.alert = Some("Water level low".to_string());
+        assert_snapshot("live_alert", &render(Screen::Live, &model));
+    }
+
+    #[test]
+    fn live_without_readings() {
+        assert_snapshot("live_empty", &render(Screen::Live, &Model::default()));
+    }
+
+    #[test]
+    fn shot() {
+        assert_snapshot("shot", &render(Screen::Shot, &brewing()));
+        assert_snapshot("shot_empty", &render(Screen::Shot, &Model::default()));
+    }
+
+    #[test]
+    fn drink() {
+        assert_snapshot("drink", &render(Screen::Drink, &brewing()));
+        let model = Model {
+            drink: Some("Double ristretto with a long preinfusion for light roasts".to_string()),
+            ..Default::default()
+        };
+        assert_snapshot("drink_long", &render(Screen::Drink, &model));
+        assert_snapshot("drink_none", &render(Screen::Drink, &Model::default()));
+    }
+
+    #[test]
+    fn alerts() {
+        let model = Model {
+            alert: Some(
+                "Boiler probe open circuit, heating is off until it reads again".to_string(),
+            ),
+            ..Default::default()
+        };
+        assert_snapshot("alerts", &render(Screen::Alerts, &model));
+        assert_snapshot("alerts_none", &render(Screen::Alerts, &Model::default()));
+    }
+
+    #[test]
+    fn screens_cycle() {
+        let mut screen = Screen::default();
+        for expected in [Screen::Shot, Screen::Drink, Screen::Alerts, Screen::Live] {
+            screen = screen.next();
+            assert_eq!(screen, expected);
+        }
+        assert_eq!(Screen::Live.previous(), Screen::Alerts);
+    }
+
+    #[test]
+    fn shot_keeps_the_latest_samples() {
+        let mut model = Model::default();
+        for weight in 0..MAX_SAMPLES + 10 {
+            model.record(Sample {
+                pressure: 9.0,
+                weight: weight as f32,
+            });
+        }
+        assert_eq!(model.shot.len(), MAX_SAMPLES);
+        assert_eq!(model.shot[0].weight, 10.0);
+    }
+
+    #[test]
+    fn wrap_on_words() {
+        assert_eq!(wrap("one two three", 7), vec!["one two", "three"]);
+        assert_eq!(wrap("  ", 7), Vec::<String>::new());
+        assert_eq!(wrap("unbreakable", 4), vec!["unbreakable"]);
+    }
+}
@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const MAX_MAPPINGS: usize = 8;
+
+// How long each of brew, hot water and steam has been on, None while it's off
+pub type Held = [Option<Duration>; 3];
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum SwitchesState {
+    Idle,
+    Brew,
+    Favourite,
+    HotWater,
+    Steam,
+    AutoTune,
+    Backflush,
+}
+
+impl std::fmt::Display for SwitchesState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            Self::Idle => "Idle",
+            Self::Brew => "Brew",
+            Self::Favourite => "Favourite",
+            Self::HotWater => "Hot Water",
+            Self::Steam => "Steam",
+            Self::AutoTune => "Autotune",
+            Self::Backflush => "Backflush",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+// The switches that have to be on for a mapping to apply, any others are ignored
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Combination {
+    pub brew: bool,
+    pub hot_water: bool,
+    pub steam: bool,
+}
+
+impl Combination {
+    fn required(&self) -> [bool; 3] {
+        [self.brew, self.hot_water, self.steam]
+    }
+
+    // Every switch `other` needs is one this needs too
+    fn includes(&self, other: &Combination) -> bool {
+        self.required()
+            .iter()
+            .zip(other.required())
+            .all(|(this, other)| *this || !other)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Gesture {
+    Switched,
+    Held(Duration),
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Mapping {
+    pub combination: Combination,
+    pub gesture: Gesture,
+    pub action: SwitchesState,
+}
+
+impl Mapping {
+    const fn new(
+        brew: bool,
+        hot_water: bool,
+        steam: bool,
+        gesture: Gesture,
+        action: SwitchesState,
+    ) -> Self {
+        Self {
+            combination: Combination {
+                brew,
+                hot_water,
+                steam,
+            },
+            gesture,
+            action,
+        }
+    }
+
+    // Mappings are checked in order so more specific combinations and longer holds go first.
+    // The switches are rockers that stay on through a shot, so no hold shares its switches
+    // with a Switched mapping or the Switched one could only ever be flicked.
+    pub const DEFAULT: [Option<Mapping>; MAX_MAPPINGS] = [
+        Some(Self::new(
+            true,
+            true,
+            true,
+            Gesture::Switched,
+            SwitchesState::AutoTune,
+        )),
+        Some(Self::new(
+            true,
+            true,
+            false,
+            Gesture::Switched,
+            SwitchesState::Backflush,
+        )),
+        Some(Self::new(
+            true,
+            false,
+            true,
+            Gesture::Held(Duration::from_secs(3)),
+            SwitchesState::Favourite,
+        )),
+        Some(Self::new(
+            true,
+            false,
+            false,
+            Gesture::Switched,
+            SwitchesState::Brew,
+        )),
+        Some(Self::new(
+            false,
+            true,
+            false,
+            Gesture::Switched,
+            SwitchesState::HotWater,
+        )),
+        Some(Self::new(
+            false,
+            false,
+            true,
+            Gesture::Switched,
+            SwitchesState::Steam,
+        )),
+        None,
+        None,
+    ];
+
+    // How long every switch in the combination has been on, None if any of them is off
+    fn held_for(&self, held: &Held) -> Option<Duration> {
+        let required = self.combination.required();
+        if required.iter().all(|required| !required) {
+            return None;
+        }
+
+        required
+            .iter()
+            .zip(held)
+            .filter(|(required, _)| **required)
+            .try_fold(Duration::MAX, |shortest, (_, active)| {
+                active.map(|active| shortest.min(active))
+            })
+    }
+
+    fn matches(&self, held: &Held) -> bool {
+        match (self.held_for(held), self.gesture) {
+            (Some(_), Gesture::Switched) => true,
+            (Some(held), Gesture::Held(hold_time)) => held >= hold_time,
+            (None, _) => false,
+        }
+    }
+
+    // A hold on at least the same switches that hasn't been reached yet, so it could still
+    // replace `switched`
+    fn pending_over(&self, switched: &Mapping, held: &Held) -> bool {
+        match self.gesture {
+            Gesture::Held(hold_time) => {
+                self.combination.includes(&switched.combination)
+                    && self.held_for(held).is_some_and(|held| held < hold_time)
+            }
+            Gesture::Switched => false,
+        }
+    }
+}
+
+// Works out the action from the switches as they are now, with nothing carried over, so with
+// every switch off it's always Idle. A Switched mapping waits while a hold that could replace it
+// is still counting: the hold wins once it's reached, and if the switches go off first the
+// Switched action has had its turn and there's nothing left to run.
+pub fn resolve(mapping: &[Option<Mapping>; MAX_MAPPINGS], held: &Held) -> SwitchesState {
+    let mut mappings = mapping.iter().flatten();
+    let Some(found) = mappings.clone().find(|mapping| mapping.matches(held)) else {
+        return SwitchesState::Idle;
+    };
+
+    if found.gesture == Gesture::Switched
+        && mappings.any(|mapping| mapping.pending_over(found, held))
+    {
+        return SwitchesState::Idle;
+    }
+    found.action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFF: Option<Duration> = None;
+
+    fn on(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    fn mapping(mappings: &[Mapping]) -> [Option<Mapping>; MAX_MAPPINGS] {
+        let mut mapping = [None; MAX_MAPPINGS];
+        for (slot, m) in mapping.iter_mut().zip(mappings) {
+            *slot = Some(*m);
+        }
+        mapping
+    }
+
+    // Runs a sequence of switch readings through the resolver
+    fn run(mapping: &[Option<Mapping>; MAX_MAPPINGS], steps: &[Held]) -> Vec<SwitchesState> {
+        steps.iter().map(|held| resolve(mapping, held)).collect()
+    }
+
+    #[test]
+    fn all_off_is_idle() {
+        assert_eq!(
+            resolve(&Mapping::DEFAULT, &[OFF, OFF, OFF]),
+            SwitchesState::Idle
+        );
+    }
+
+    #[test]
+    fn brew_rocker_brews_for_the_whole_shot() {
+        let states = run(
+            &Mapping::DEFAULT,
+            &[
+                [on(0), OFF, OFF],
+                [on(5_000), OFF, OFF],
+                [on(30_000), OFF, OFF],
+                [OFF, OFF, OFF],
+            ],
+        );
+        use SwitchesState::*;
+        assert_eq!(states, [Brew, Brew, Brew, Idle]);
+    }
+
+    #[test]
+    fn flick() {
+        let states = run(
+            &Mapping::DEFAULT,
+            &[[OFF, OFF, on(0)], [OFF, OFF, on(200)], [OFF, OFF, OFF]],
+        );
+        use SwitchesState::*;
+        assert_eq!(states, [Steam, Steam, Idle]);
+    }
+
+    #[test]
+    fn long_hold_reaches_the_favourite() {
+        let states = run(
+            &Mapping::DEFAULT,
+            &[
+                [on(0), OFF, on(0)],
+                [on(2_999), OFF, on(2_999)],
+                [on(3_000), OFF, on(3_000)],
+                [on(20_000), OFF, on(20_000)],
+                [OFF, OFF, OFF],
+            ],
+        );
+        use SwitchesState::*;
+        // Neither Brew nor Steam start while the hold could still turn into the favourite
+        assert_eq!(states, [Idle, Idle, Favourite, Favourite, Idle]);
+    }
+
+    #[test]
+    fn release_before_the_hold_time_never_latches() {
+        let shared = mapping(&[
+            Mapping::new(
+                true,
+                false,
+                false,
+                Gesture::Held(Duration::from_secs(3)),
+                SwitchesState::Favourite,
+            ),
+            Mapping::new(true, false, false, Gesture::Switched, SwitchesState::Brew),
+        ]);
+        let states = run(
+            &shared,
+            &[
+                [on(0), OFF, OFF],
+                [on(1_000), OFF, OFF],
+                [OFF, OFF, OFF],
+                [OFF, OFF, OFF],
+            ],
+        );
+        assert!(states.iter().all(|state| *state == SwitchesState::Idle));
+        assert_eq!(
+            resolve(&shared, &[on(3_000), OFF, OFF]),
+            SwitchesState::Favourite
+        );
+    }
+
+    #[test]
+    fn most_specific_combination_wins() {
+        use SwitchesState::*;
+        assert_eq!(
+            resolve(&Mapping::DEFAULT, &[on(100), on(100), OFF]),
+            Backflush
+        );
+        assert_eq!(
+            resolve(&Mapping::DEFAULT, &[on(100), on(100), on(100)]),
+            AutoTune
+        );
+        assert_eq!(resolve(&Mapping::DEFAULT, &[OFF, on(100), OFF]), HotWater);
+    }
+
+    #[test]
+    fn default_holds_share_no_switches_with_a_switched_mapping() {
+        let mappings: Vec<&Mapping> = Mapping::DEFAULT.iter().flatten().collect();
+        for hold in mappings
+            .iter()
+            .filter(|m| matches!(m.gesture, Gesture::Held(_)))
+        {
+            assert!(!mappings
+                .iter()
+                .any(|m| m.gesture == Gesture::Switched && m.combination == hold.combination));
+        }
+    }
+}
@@ -1,5 +1,17 @@
+use crate::board::Board;
+use crate::components::descale::Message as DescaleMessage;
+use crate::gpio::switch::SwitchesState;
+use crate::indicator::theme::Theme;
+use crate::maintenance::Maintenance;
+use crate::schemas::command::Command;
+use crate::sensors::pressure::{Preset as PressurePreset, Transducer};
+use crate::sensors::pt100::CalibrationPoint;
+use crate::sensors::scale::CalibrationStep;
+use crate::state_machines::operational_fsm::OperationalState;
+use crate::types::Temperature;
 use crate::{app_state::System, config::Config};
 use anyhow::Result;
+use serde::Deserialize;
 use serde_json::Value;
 
 pub fn version() -> &'static str {
@@ -33,3 +45,217 @@ pub fn set_config(data: &str, system: System) -> Result<Value> {
     });
     Ok(value)
 }
+
+pub fn get_maintenance(system: System) -> Result<Value> {
+    let maintenance = Maintenance::load_or_default(&system.config.read().unwrap().nvs);
+    Ok(serde_json::to_value(maintenance)?)
+}
+
+pub fn command(data: &str, system: System) -> Result<()> {
+    let command: Command = serde_json::from_str(data)?;
+    system.execute(command);
+    Ok(())
+}
+
+pub fn get_theme(system: System) -> Result<Value> {
+    let theme = system.config.read().unwrap().indicator.theme;
+    Ok(serde_json::to_value(theme)?)
+}
+
+pub fn set_theme(data: &str, system: System) -> Result<Value> {
+    let theme: Theme = serde_json::from_str(data)?;
+    system.set_theme(theme)?;
+    Ok(serde_json::to_value(theme)?)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DescaleAction {
+    Start,
+    Continue,
+    Abort,
+}
+
+#[derive(Deserialize)]
+struct DescaleRequest {
+    action: DescaleAction,
+}
+
+pub fn get_descale(system: System) -> Result<Value> {
+    let maintenance = Maintenance::load_or_default(&system.config.read().unwrap().nvs);
+    let operation = system.operational_state.lock().unwrap().to_report();
+    Ok(serde_json::json!({
+        "last_descale": maintenance.last_descale,
+        "operation": operation,
+    }))
+}
+
+pub fn descale(data: &str, system: System) -> Result<()> {
+    let request: DescaleRequest = serde_json::from_str(data)?;
+    let message = match request.action {
+        DescaleAction::Start => {
+            let config = system.config.read().unwrap();
+            config
+                .descale
+                .validate(&config.level_sensor.tank)
+                .map_err(anyhow::Error::msg)?;
+            DescaleMessage::Start
+        }
+        DescaleAction::Continue => DescaleMessage::Continue,
+        DescaleAction::Abort => DescaleMessage::Abort,
+    };
+    system.board.descale.send_message(message);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct CalibrationRequest {
+    reference: Temperature,
+}
+
+pub fn get_pt100_calibration(system: System) -> Result<Value> {
+    let reading = *system.board.temperature_raw.read().unwrap();
+    let temperature = system.board.sensors.temperature.get();
+    let calibration = system.config.read().unwrap().boiler.pt100_calibration;
+    Ok(serde_json::json!({
+        "reading": reading,
+        "temperature": temperature,
+        "calibration": calibration,
+    }))
+}
+
+pub fn add_pt100_calibration_point(data: &str, system: System) -> Result<Value> {
+    let request: CalibrationRequest = serde_json::from_str(data)?;
+    let point = CalibrationPoint {
+        reading: *system.board.temperature_raw.read().unwrap() as f32,
+        reference: request.reference,
+    };
+
+    let mut config = system.config.write().unwrap();
+    {
+        let probe = system.board.temperature_probe.read().unwrap();
+        config
+            .boiler
+            .pt100_calibration
+            .add_point(point, |reading| probe.resistance(reading))
+            .map_err(anyhow::Error::msg)?;
+    }
+    *system.board.temperature_probe.write().unwrap() =
+        Board::create_temperature_probe(&config.boiler);
+    config.save()?;
+
+    Ok(serde_json::to_value(config.boiler.pt100_calibration)?)
+}
+
+pub fn clear_pt100_calibration(system: System) -> Result<()> {
+    let mut config = system.config.write().unwrap();
+    config.boiler.pt100_calibration.clear();
+    *system.board.temperature_probe.write().unwrap() =
+        Board::create_temperature_probe(&config.boiler);
+    config.save()?;
+    Ok(())
+}
+
+pub fn get_scale_calibration(system: System) -> Result<Value> {
+    let scaling = system.config.read().unwrap().load_cell.scaling;
+    Ok(serde_json::json!({
+        "weight": system.board.sensors.weight.get(),
+        "scaling": scaling,
+    }))
+}
+
+pub fn calibrate_scale(data: &str, system: System) -> Result<Value> {
+    let step: CalibrationStep = serde_json::from_str(data)?;
+    let scaling = system
+        .board
+        .scale
+        .calibrate(step)
+        .map_err(anyhow::Error::msg)?;
+
+    let mut config = system.config.write().unwrap();
+    if let Some(scaling) = scaling {
+        config.load_cell.scaling = scaling;
+        config.save()?;
+    }
+
+    Ok(serde_json::json!({
+        "scaling": config.load_cell.scaling,
+        "complete": scaling.is_some(),
+    }))
+}
+
+pub fn get_water(system: System) -> Result<Value> {
+    let tank = system.config.read().unwrap().level_sensor.tank;
+    let level = system.board.sensors.level.get();
+    let consumption = *system.board.level_sensor.consumption.read().unwrap();
+    Ok(serde_json::json!({
+        "level": level,
+        "volume": tank.volume(level),
+        "fill": tank.percentage(level),
+        "consumption": consumption,
+    }))
+}
+
+pub fn get_sensors(system: System) -> Result<Value> {
+    Ok(serde_json::to_value(system.board.sensors.readings())?)
+}
+
+pub fn get_temperature_probes(system: System) -> Result<Value> {
+    let discovered = system.board.discovered_probes.read().unwrap().clone();
+    Ok(serde_json::to_value(discovered)?)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TransducerRequest {
+    Preset { preset: PressurePreset },
+    Custom(Transducer),
+}
+
+pub fn get_pressure_calibration(system: System) -> Result<Value> {
+    let voltage = *system.board.pressure_voltage.read().unwrap();
+    let pressure = system.board.sensors.pressure.get();
+    let transducer = *system.board.pressure_sensor.read().unwrap();
+    Ok(serde_json::json!({
+        "voltage": voltage,
+        "pressure": pressure,
+        "transducer": transducer,
+    }))
+}
+
+pub fn set_pressure_transducer(data: &str, system: System) -> Result<Value> {
+    let transducer = match serde_json::from_str(data)? {
+        TransducerRequest::Preset { preset } => Transducer::from(preset),
+        TransducerRequest::Custom(transducer) => transducer,
+    };
+    if transducer.output_max <= transducer.output_min || transducer.divider <= 0.0 {
+        return Err(anyhow::anyhow!("Invalid pressure transducer configuration"));
+    }
+
+    let mut config = system.config.write().unwrap();
+    config.pressure_sensor = transducer;
+    *system.board.pressure_sensor.write().unwrap() = transducer;
+    config.save()?;
+
+    Ok(serde_json::to_value(transducer)?)
+}
+
+// The offset is whatever the transducer reads at atmospheric pressure, so the pump
+// has to be off and the group open
+pub fn zero_pressure(system: System) -> Result<Value> {
+    if !matches!(
+        *system.operational_state.lock().unwrap(),
+        OperationalState::Idle
+    ) || system.board.switches.get_state() != SwitchesState::Idle
+    {
+        return Err(anyhow::anyhow!("Turn the pump off before zeroing"));
+    }
+
+    let voltage = *system.board.pressure_voltage.read().unwrap();
+    let mut config = system.config.write().unwrap();
+    config.pressure_sensor.zero_offset = config.pressure_sensor.convert_uncorrected(voltage);
+    *system.board.pressure_sensor.write().unwrap() = config.pressure_sensor;
+    config.save()?;
+
+    Ok(serde_json::to_value(config.pressure_sensor)?)
+}
@@ -1,6 +1,10 @@
+#[cfg(feature = "sdcard")]
+use super::query;
 use crate::app_state::System;
 #[cfg(feature = "sdcard")]
-use crate::schemas::drink::Drink;
+use crate::schemas::drink::{Drink, Storage};
+#[cfg(feature = "sdcard")]
+use crate::schemas::formats::{Converted, Format};
 use anyhow::Result;
 
 #[cfg(feature = "sdcard")]
@@ -67,6 +71,44 @@ pub fn get_drink(data: &str, system: System) -> Result<serde_json::Value> {
     }
 }
 
+#[cfg(feature = "sdcard")]
+fn format(uri: &str) -> Result<Format> {
+    query(uri, "format")
+        .ok_or(anyhow::anyhow!("Missing format"))?
+        .parse()
+}
+
+// Saved like any other drink, with the warnings passed back so nothing is lost silently
+#[cfg(feature = "sdcard")]
+pub fn import_drink(uri: &str, data: &str, system: System) -> Result<Converted<Drink>> {
+    if !*system.sd_card_present {
+        return Err(Error::System("No SD card present".to_string()).into());
+    }
+    let converted = format(uri)?.import(data)?;
+    let mut menu = system
+        .menu
+        .write()
+        .map_err(|_| Error::System("Failed to write menu".to_string()))?;
+    converted.value.save(&mut menu)?;
+    Ok(converted)
+}
+
+#[cfg(feature = "sdcard")]
+pub fn export_drink(uri: &str, system: System) -> Result<Converted<serde_json::Value>> {
+    if !*system.sd_card_present {
+        return Err(Error::System("No SD card present".to_string()).into());
+    }
+    let name = query(uri, "name").ok_or(anyhow::anyhow!("Missing drink name"))?;
+    let drink = {
+        let menu = system
+            .menu
+            .read()
+            .map_err(|_| Error::System("Failed to read menu".to_string()))?;
+        Drink::load_drink(&name, &menu)?
+    };
+    format(uri)?.export(&drink)
+}
+
 pub fn post_drink(_data: &str, _system: System) -> Result<()> {
     todo!();
 }
@@ -0,0 +1,122 @@
+use super::query;
+use crate::app_state::System;
+use crate::components::shot_log::{Annotation, Kind, ShotLog};
+use crate::types::{Bar, Grams, Temperature};
+use anyhow::Result;
+use serde::Serialize;
+
+const DEFAULT_PAGE_SIZE: usize = 10;
+const MAX_PAGE_SIZE: usize = 50;
+
+#[derive(Serialize)]
+pub struct Summary {
+    id: u32,
+    kind: Kind,
+    started: u64,
+    drink: Option<String>,
+    dose: Option<Grams>,
+    #[serde(rename = "yield")]
+    yield_weight: Grams,
+    time: f32,
+    ratio: Option<f32>,
+    peak_pressure: Bar,
+    average_temperature: Temperature,
+    rating: Option<u8>,
+}
+
+#[derive(Serialize)]
+pub struct Page {
+    page: usize,
+    per_page: usize,
+    total: usize,
+    shots: Vec<Summary>,
+}
+
+pub enum Format {
+    Json,
+    Csv,
+}
+
+// The id is whatever follows `/api/v1/shots/`, before any query string
+pub fn id(uri: &str) -> Result<u32> {
+    let path = uri.split('?').next().unwrap_or_default();
+    path.rsplit('/')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .ok_or(anyhow::anyhow!("Invalid shot id"))
+}
+
+pub fn format(uri: &str) -> Result<Format> {
+    match query(uri, "format").as_deref() {
+        None | Some("json") => Ok(Format::Json),
+        Some("csv") => Ok(Format::Csv),
+        Some(other) => Err(anyhow::anyhow!("Unknown format {}", other)),
+    }
+}
+
+fn sd_card(system: &System) -> Result<()> {
+    if *system.sd_card_present {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("No SD card present"))
+    }
+}
+
+// Newest first
+pub fn list(uri: &str, system: System) -> Result<Page> {
+    sd_card(&system)?;
+    let page: usize = query(uri, "page").as_deref().unwrap_or("0").parse()?;
+    let per_page = query(uri, "per_page")
+        .map(|per_page| per_page.parse())
+        .transpose()?
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let numbers = ShotLog::numbers()?;
+    let shots = numbers
+        .iter()
+        .rev()
+        .skip(page * per_page)
+        .take(per_page)
+        .map(|&id| {
+            let header = ShotLog::load_header(id)?;
+            Ok(Summary {
+                id,
+                kind: header.kind,
+                started: header.started,
+                drink: header.drink,
+                dose: header.annotation.dose,
+                yield_weight: header.summary.final_weight,
+                time: header.summary.duration.as_secs_f32(),
+                ratio: header.summary.metrics.brew_ratio,
+                peak_pressure: header.summary.metrics.peak_pressure,
+                average_temperature: header.summary.mean_temperature,
+                rating: header.annotation.rating,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Page {
+        page,
+        per_page,
+        total: numbers.len(),
+        shots,
+    })
+}
+
+pub fn get(id: u32, system: System) -> Result<ShotLog> {
+    sd_card(&system)?;
+    ShotLog::load(id)
+}
+
+pub fn delete(id: u32, system: System) -> Result<()> {
+    sd_card(&system)?;
+    ShotLog::delete(id)
+}
+
+pub fn annotate(id: u32, data: &str, system: System) -> Result<serde_json::Value> {
+    sd_card(&system)?;
+    let annotation: Annotation = serde_json::from_str(data)?;
+    let header = ShotLog::annotate(id, annotation)?;
+    Ok(serde_json::to_value(header)?)
+}
@@ -47,7 +47,7 @@ impl HomeAssistantIntegration {
                     "p": "sensor",
                     "device_class": "temperature",
                     "unit_of_measurement": "°C",
-                    "value_template": "{{ value_json.device.temperature}}",
+                    "value_template": "{{ value_json.device.temperature.value }}",
                     "name": "Boiler Temperature",
                     "unique_id": "temperature_boiler"
                 },
@@ -55,16 +55,40 @@ impl HomeAssistantIntegration {
                     "p": "sensor",
                     "device_class": "temperature",
                     "unit_of_measurement": "°C",
-                    "value_template": "{{ value_json.device.ambient}}",
+                    "value_template": "{{ value_json.device.ambient.value }}",
                     "unique_id": "temperature_ambient",
                     "name": "Ambient Temperature"
                 },
+                "group_head_temperature": {
+                    "p": "sensor",
+                    "device_class": "temperature",
+                    "unit_of_measurement": "°C",
+                    "value_template": "{{ value_json.device.probes.group_head}}",
+                    "unique_id": "temperature_group_head",
+                    "name": "Group Head Temperature"
+                },
+                "steam_wand_temperature": {
+                    "p": "sensor",
+                    "device_class": "temperature",
+                    "unit_of_measurement": "°C",
+                    "value_template": "{{ value_json.device.probes.steam_wand}}",
+                    "unique_id": "temperature_steam_wand",
+                    "name": "Steam Wand Temperature"
+                },
+                "enclosure_temperature": {
+                    "p": "sensor",
+                    "device_class": "temperature",
+                    "unit_of_measurement": "°C",
+                    "value_template": "{{ value_json.device.probes.enclosure}}",
+                    "unique_id": "temperature_enclosure",
+                    "name": "Enclosure Temperature"
+                },
                 "pump": {
                     "name": "Pressure",
                     "p": "sensor",
                     "device_class": "pressure",
                     "unit_of_measurement": "bar",
-                    "value_template": "{{ value_json.device.pressure}}",
+                    "value_template": "{{ value_json.device.pressure.value }}",
                     "unique_id": "pressure_pump"
                 },
                 "reservoir_level": {
@@ -73,9 +97,17 @@ impl HomeAssistantIntegration {
                     "p": "sensor",
                     "device_class": "volume_storage",
                     "unit_of_measurement": "mL",
-                    "value_template": "{{ value_json.device.level}}",
+                    "value_template": "{{ value_json.device.volume}}",
                     "unique_id": "level_reservoir"
                 },
+                "reservoir_fill": {
+                    "name": "Water Level Percent",
+                    "icon": "mdi:water-percent",
+                    "p": "sensor",
+                    "unit_of_measurement": "%",
+                    "value_template": "{{ value_json.device.fill}}",
+                    "unique_id": "fill_reservoir"
+                },
                 "power": {
                     "name": "Power",
                     "p": "sensor",
@@ -88,7 +120,7 @@ impl HomeAssistantIntegration {
                     "p": "sensor",
                     "device_class": "weight",
                     "unit_of_measurement": "g",
-                    "value_template": "{{ value_json.device.weight}}",
+                    "value_template": "{{ value_json.device.weight.value }}",
                     "unique_id": "level_reservoir"
                 },
                 "switch_brew": {
@@ -112,11 +144,29 @@ impl HomeAssistantIntegration {
                     "value_template": "{{ 'ON' if value_json.device.switches.steam else 'OFF'}}",
                     "unique_id": "switch_pump"
                 },
+                "last_shot": {
+                    "name": "Last Shot",
+                    "icon": "mdi:coffee",
+                    "p": "sensor",
+                    "device_class": "duration",
+                    "unit_of_measurement": "s",
+                    "value_template": "{{ value_json.shot.contact_time if value_json.shot is defined else none }}",
+                    "json_attributes_topic": format!("{}/{}/state", name_lc, id),
+                    "json_attributes_template": "{{ value_json.shot | default({}) | tojson }}",
+                    "unique_id": "last_shot"
+                },
+                "last_shot_ratio": {
+                    "name": "Last Shot Ratio",
+                    "icon": "mdi:scale-balance",
+                    "p": "sensor",
+                    "value_template": "{{ value_json.shot.brew_ratio if value_json.shot is defined else none }}",
+                    "unique_id": "last_shot_ratio"
+                },
                 "boiler_target": {
                     "p": "number",
                     "device_class": "temperature",
                     "unit_of_measurement": "°C",
-                    "value_template": "{{ value_json.device.temperature}}",
+                    "value_template": "{{ value_json.device.temperature.value }}",
                     "unique_id": "boiler_target",
                     "command_topic": format!("{}/{}/set/temperature", name_lc, id),
                     "max": 140,
@@ -127,7 +177,7 @@ impl HomeAssistantIntegration {
                     "p": "number",
                     "device_class": "pressure",
                     "unit_of_measurement": "bar",
-                    "value_template": "{{ value_json.device.pressure}}",
+                    "value_template": "{{ value_json.device.pressure.value }}",
                     "unique_id": "pump_target",
                     "command_topic": format!("{}/{}/set/pressure", name_lc, id),
                     "max": 12,
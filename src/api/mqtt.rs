@@ -1,16 +1,9 @@
 use crate::api::home_assistant::HomeAssistantIntegration;
 use crate::app_state::System;
 use crate::config::Mqtt as Config;
+use crate::schemas::command::Command;
 use esp_idf_svc::mqtt::client::*;
 
-#[derive(Debug)]
-pub enum Command {
-    PowerOn,
-    PowerOff,
-    SetTemperature(f32),
-    SetPressure(f32),
-}
-
 impl<E> TryFrom<&EventPayload<'_, E>> for Command
 where
     E: std::fmt::Debug,
@@ -43,6 +36,22 @@ where
                 "pressure" => Ok(Command::SetPressure(
                     payload.parse().map_err(|_| "Invalid pressure")?,
                 )),
+                "tare" => Ok(Command::Tare),
+                "drink" => Ok(Command::CycleDrink),
+                "shot" => match payload.to_lowercase().as_str() {
+                    "start" => Ok(Command::StartShot),
+                    "stop" => Ok(Command::StopShot),
+                    _ => Err("Invalid shot command"),
+                },
+                "steam" => Ok(Command::ToggleSteam),
+                "screen" => match payload.to_lowercase().as_str() {
+                    "next" => Ok(Command::NextScreen),
+                    "previous" => Ok(Command::PreviousScreen),
+                    _ => Err("Invalid screen command"),
+                },
+                "theme" => Ok(Command::SetTheme(
+                    serde_json::from_str(&payload).map_err(|_| "Invalid theme")?,
+                )),
                 _ => Err("Invalid command"),
             }
         } else {
@@ -51,21 +60,6 @@ where
     }
 }
 
-impl Command {
-    fn execute(&self, system: &System) {
-        log::info!("Executing command: {:?}", self);
-        match self {
-            Command::PowerOn => system.set_temperature(60.0),
-            Command::PowerOff => {
-                system.set_temperature(0.0);
-                system.set_pressure(0.0);
-            }
-            Command::SetTemperature(temperature) => system.set_temperature(*temperature),
-            Command::SetPressure(pressure) => system.set_pressure(*pressure),
-        }
-    }
-}
-
 pub fn mqtt_create(config: Config, system: &System) {
     let system = system.clone();
     let event_topic = config
@@ -159,8 +153,19 @@ fn mqtt_event_handler(event: &EspMqttEvent, system: System) {
     log::debug!("Event: {:?}", event.payload());
 
     let payload = event.payload();
+    match payload {
+        EventPayload::Connected(_) => {
+            system.board.network.set_mqtt(true);
+            return;
+        }
+        EventPayload::Disconnected => {
+            system.board.network.set_mqtt(false);
+            return;
+        }
+        _ => {}
+    }
     match Command::try_from(&payload) {
-        Ok(command) => command.execute(&system),
+        Ok(command) => system.execute(command),
         Err(e) => {
             log::error!("Failed to parse command: {}", e);
         }
@@ -1,3 +1,5 @@
+#[cfg(feature = "sdcard")]
+use super::handlers_shots::{self, Format};
 use super::{handlers_device, handlers_drinks};
 use crate::app_state::System;
 use anyhow::{Error, Result};
@@ -9,12 +11,18 @@ use esp_idf_svc::http::server::EspHttpServer;
 
 const STACK_SIZE: usize = 1024 * 10;
 const MAX_LEN: usize = 2048;
+// Profiles from other machines carry notes and metadata we don't keep
+#[cfg(feature = "sdcard")]
+const MAX_PROFILE_LEN: usize = 8 * 1024;
 
 macro_rules! handle_request_data {
-    ($req:expr) => {{
+    ($req:expr) => {
+        handle_request_data!($req, MAX_LEN)
+    };
+    ($req:expr, $max_len:expr) => {{
         let len = $req.content_len().unwrap_or(0) as usize;
 
-        if len > MAX_LEN {
+        if len > $max_len {
             $req.into_status_response(413)?
                 .write_all("Request too big".as_bytes())?;
             return Ok(());
@@ -49,6 +57,17 @@ macro_rules! ok_with_json {
     }};
 }
 
+// Each chunk goes out as it's written, so large bodies never have to be built in memory
+macro_rules! ok_with_chunks {
+    ($req:expr, $content_type:expr, $chunks:expr) => {{
+        let mut response = $req.into_response(200, None, &[("Content-Type", $content_type)])?;
+        for chunk in $chunks {
+            response.write_all(chunk?.as_bytes())?;
+        }
+        Ok(())
+    }};
+}
+
 macro_rules! bad_request {
     ($req:expr, $err:expr) => {{
         $req.into_status_response(400)?
@@ -60,6 +79,7 @@ macro_rules! bad_request {
 pub fn create_server(system: System) -> Result<EspHttpServer<'static>> {
     let server_configuration = esp_idf_svc::http::server::Configuration {
         stack_size: STACK_SIZE,
+        uri_match_wildcard: true,
         ..Default::default()
     };
 
@@ -90,6 +110,58 @@ fn create_router(server: &mut EspHttpServer<'static>, system: System) -> Result<
         ok!(req)
     })?;
 
+    /* Shot History Endpoints */
+    #[cfg(feature = "sdcard")]
+    {
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>("/api/v1/shots", Method::Get, move |req| {
+            let uri = req.uri().to_string();
+            match handlers_shots::list(&uri, my_system.clone()) {
+                Ok(data) => ok_with_json!(req, data),
+                Err(e) => bad_request!(req, e),
+            }
+        })?;
+
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>("/api/v1/shots/*", Method::Get, move |req| {
+            let uri = req.uri().to_string();
+            let shot =
+                handlers_shots::id(&uri).and_then(|id| handlers_shots::get(id, my_system.clone()));
+            match (shot, handlers_shots::format(&uri)) {
+                (Ok(shot), Ok(Format::Csv)) => {
+                    ok_with_chunks!(req, "text/csv", shot.csv_chunks().map(Ok::<_, Error>))
+                }
+                (Ok(shot), Ok(Format::Json)) => {
+                    ok_with_chunks!(req, "application/json", shot.json_chunks())
+                }
+                (Err(e), _) | (_, Err(e)) => bad_request!(req, e),
+            }
+        })?;
+
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>("/api/v1/shots/*", Method::Put, move |mut req| {
+            let data = handle_request_data!(req);
+            let uri = req.uri().to_string();
+            match handlers_shots::id(&uri)
+                .and_then(|id| handlers_shots::annotate(id, &data, my_system.clone()))
+            {
+                Ok(value) => ok_with_json!(req, value),
+                Err(e) => bad_request!(req, e),
+            }
+        })?;
+
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>("/api/v1/shots/*", Method::Delete, move |req| {
+            let uri = req.uri().to_string();
+            match handlers_shots::id(&uri)
+                .and_then(|id| handlers_shots::delete(id, my_system.clone()))
+            {
+                Ok(_) => ok!(req),
+                Err(e) => bad_request!(req, e),
+            }
+        })?;
+    }
+
     /* Drink Endpoints */
     #[cfg(feature = "sdcard")]
     {
@@ -116,6 +188,32 @@ fn create_router(server: &mut EspHttpServer<'static>, system: System) -> Result<
         })?;
     }
 
+    #[cfg(feature = "sdcard")]
+    {
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>(
+            "/api/v1/coffee/drink/import",
+            Method::Put,
+            move |mut req| {
+                let data = handle_request_data!(req, MAX_PROFILE_LEN);
+                let uri = req.uri().to_string();
+                match handlers_drinks::import_drink(&uri, &data, my_system.clone()) {
+                    Ok(converted) => ok_with_json!(req, converted),
+                    Err(e) => bad_request!(req, e),
+                }
+            },
+        )?;
+
+        let my_system = system.clone();
+        server.fn_handler::<Error, _>("/api/v1/coffee/drink/export", Method::Get, move |req| {
+            let uri = req.uri().to_string();
+            match handlers_drinks::export_drink(&uri, my_system.clone()) {
+                Ok(converted) => ok_with_json!(req, converted),
+                Err(e) => bad_request!(req, e),
+            }
+        })?;
+    }
+
     let my_system = system.clone();
     server.fn_handler::<Error, _>("/api/v1/coffee/drink", Method::Post, move |mut req| {
         let data = handle_request_data!(req);
@@ -142,5 +240,169 @@ fn create_router(server: &mut EspHttpServer<'static>, system: System) -> Result<
         }
     })?;
 
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/maintenance", Method::Get, move |req| {
+        match handlers_device::get_maintenance(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/command", Method::Post, move |mut req| {
+        let data = handle_request_data!(req);
+        match handlers_device::command(&data, my_system.clone()) {
+            Ok(_) => ok!(req),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/theme", Method::Get, move |req| {
+        match handlers_device::get_theme(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/theme", Method::Put, move |mut req| {
+        let data = handle_request_data!(req);
+        match handlers_device::set_theme(&data, my_system.clone()) {
+            Ok(value) => ok_with_json!(req, value),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/descale", Method::Get, move |req| {
+        match handlers_device::get_descale(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/descale", Method::Post, move |mut req| {
+        let data = handle_request_data!(req);
+        match handlers_device::descale(&data, my_system.clone()) {
+            Ok(_) => ok!(req),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/probes", Method::Get, move |req| {
+        match handlers_device::get_temperature_probes(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/water", Method::Get, move |req| {
+        match handlers_device::get_water(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>("/api/v1/device/sensors", Method::Get, move |req| {
+        match handlers_device::get_sensors(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        }
+    })?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pt100",
+        Method::Get,
+        move |req| match handlers_device::get_pt100_calibration(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pt100",
+        Method::Post,
+        move |mut req| {
+            let data = handle_request_data!(req);
+            match handlers_device::add_pt100_calibration_point(&data, my_system.clone()) {
+                Ok(value) => ok_with_json!(req, value),
+                Err(e) => bad_request!(req, e),
+            }
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pt100",
+        Method::Delete,
+        move |req| match handlers_device::clear_pt100_calibration(my_system.clone()) {
+            Ok(_) => ok!(req),
+            Err(e) => bad_request!(req, e),
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pressure",
+        Method::Get,
+        move |req| match handlers_device::get_pressure_calibration(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pressure",
+        Method::Put,
+        move |mut req| {
+            let data = handle_request_data!(req);
+            match handlers_device::set_pressure_transducer(&data, my_system.clone()) {
+                Ok(value) => ok_with_json!(req, value),
+                Err(e) => bad_request!(req, e),
+            }
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/pressure",
+        Method::Post,
+        move |req| match handlers_device::zero_pressure(my_system.clone()) {
+            Ok(value) => ok_with_json!(req, value),
+            Err(e) => bad_request!(req, e),
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/scale",
+        Method::Get,
+        move |req| match handlers_device::get_scale_calibration(my_system.clone()) {
+            Ok(data) => ok_with_json!(req, data),
+            Err(e) => bad_request!(req, e),
+        },
+    )?;
+
+    let my_system = system.clone();
+    server.fn_handler::<Error, _>(
+        "/api/v1/device/calibration/scale",
+        Method::Post,
+        move |mut req| {
+            let data = handle_request_data!(req);
+            match handlers_device::calibrate_scale(&data, my_system.clone()) {
+                Ok(value) => ok_with_json!(req, value),
+                Err(e) => bad_request!(req, e),
+            }
+        },
+    )?;
+
     Ok(())
 }
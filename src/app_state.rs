@@ -1,17 +1,32 @@
 use crate::board::Board;
-use crate::config::Config;
+use crate::components::boiler::{Message as BoilerMessage, Mode as BoilerMode};
 #[cfg(feature = "sdcard")]
-use crate::schemas::drink::Drink;
+use crate::components::shot_log::Recorder;
+use crate::config::Config;
+use crate::indicator::onboard::StatusLed;
+use crate::indicator::theme::Theme;
+use crate::models::extraction::Metrics;
+use crate::schemas::command::Command;
 use crate::schemas::drink::Menu;
+#[cfg(feature = "sdcard")]
+use crate::schemas::drink::{Drink, Storage};
 use crate::schemas::event::EventBuffer;
 use crate::schemas::status::StatusReport;
+use crate::sensors::a02yyuw::Message as LevelMessage;
 use crate::state_machines::{
     operational_fsm::{OperationalState, Transitions as OperationalTransitions},
     system_fsm::{SystemState, Transition as SystemTransitions},
     ArcMutexState,
 };
+use crate::types::{Bar, Grams, Milliliters, Temperature};
 use std::default::Default;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::{mpsc::channel, Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const BREW_TEMPERATURE: Temperature = 94.0;
+const TARE_SAMPLES: usize = 32;
+// How long the level sensor takes to answer a read request
+const LEVEL_READ_TIME: Duration = Duration::from_millis(400);
 
 #[derive(Clone)]
 pub struct System {
@@ -26,6 +41,9 @@ pub struct System {
     #[cfg(feature = "sdcard")]
     pub sd_card_present: Arc<bool>,
     pub menu: Arc<RwLock<Menu>>,
+    pub selected_drink: Arc<RwLock<Option<u32>>>,
+    pub steam: Arc<RwLock<bool>>,
+    pub last_shot: Arc<RwLock<Option<Metrics>>>,
 }
 
 impl System {
@@ -40,7 +58,14 @@ impl System {
         );
 
         let operational_state = Arc::new(Mutex::new(OperationalState::default()));
-        let board = Board::new(operational_state.clone(), &mut config);
+        let events = Arc::new(Mutex::new(EventBuffer::new()));
+        let (commands, pending_commands) = channel();
+        let board = Board::new(
+            operational_state.clone(),
+            events.clone(),
+            commands,
+            &mut config,
+        );
 
         operational_state
             .transition(OperationalTransitions::Idle)
@@ -63,11 +88,11 @@ impl System {
         #[cfg(not(feature = "sdcard"))]
         let menu = Arc::new(RwLock::new(Menu::default()));
 
-        System {
+        let system = System {
             system_state: Arc::new(Mutex::new(SystemState::default())),
             operational_state,
             board,
-            events: Arc::new(Mutex::new(EventBuffer::new())),
+            events,
             config: Arc::new(RwLock::new(config)),
 
             echo_data: Arc::new(RwLock::new("".to_string())),
@@ -75,19 +100,45 @@ impl System {
             #[cfg(feature = "sdcard")]
             sd_card_present,
             menu,
-        }
+            selected_drink: Arc::new(RwLock::new(None)),
+            steam: Arc::new(RwLock::new(false)),
+            last_shot: Arc::new(RwLock::new(None)),
+        };
+
+        StatusLed::start(
+            system.board.onboard_rgb.clone(),
+            system.board.network.clone(),
+            system.system_state.clone(),
+        );
+
+        #[cfg(feature = "sdcard")]
+        Recorder::start(&system);
+
+        let executor = system.clone();
+        std::thread::Builder::new()
+            .name("Commands".to_string())
+            .spawn(move || {
+                for command in pending_commands {
+                    executor.execute(command);
+                }
+            })
+            .expect("Failed to spawn command thread");
+
+        system
     }
 
     pub fn generate_report(&self) -> StatusReport {
         let system_state = self.system_state.lock().unwrap().clone();
         let operational_state = self.operational_state.lock().unwrap().clone();
-        let board = self.board.generate_report();
+        let tank = self.config.read().unwrap().level_sensor.tank;
+        let board = self.board.generate_report(&tank);
 
         StatusReport {
             status: system_state.to_string(),
             message: None,
             device: board,
             operation: operational_state.to_report(),
+            shot: *self.last_shot.read().unwrap(),
         }
     }
 
@@ -144,6 +195,216 @@ impl System {
     pub fn set_pressure(&self, pressure: f32) {
         self.board.pump.set_pressure(pressure);
     }
+
+    pub fn execute(&self, command: Command) {
+        log::info!("Executing command: {:?}", command);
+        if self.refused_in_error(command) {
+            self.report_warn_event(
+                module_path!(),
+                format!("{:?} refused, the system is in an error state", command),
+            );
+            return;
+        }
+        match command {
+            Command::PowerOn => self.set_temperature(60.0),
+            Command::PowerOff => {
+                self.set_temperature(0.0);
+                self.set_pressure(0.0);
+            }
+            Command::SetTemperature(temperature) => self.set_temperature(temperature),
+            Command::SetPressure(pressure) => self.set_pressure(pressure),
+            Command::Tare => self.board.scale.tare(TARE_SAMPLES),
+            Command::CycleDrink => self.cycle_drink(),
+            Command::StartShot => self.start_shot(),
+            Command::StopShot => {
+                self.board.pump.turn_off();
+                self.board.scale.stop_brewing();
+                self.transition(OperationalTransitions::Stop);
+            }
+            Command::ShotFinished => {
+                // The pump also runs on a timer for descaling, which has its own way out
+                if matches!(
+                    *self.operational_state.lock().unwrap(),
+                    OperationalState::Brewing(_)
+                ) {
+                    self.board.scale.stop_brewing();
+                    self.transition(OperationalTransitions::Stop);
+                }
+            }
+            Command::ShotAbandoned => {
+                self.report_warn_event(
+                    module_path!(),
+                    "Shot stopped before reaching its yield".to_string(),
+                );
+                self.execute(Command::ShotFinished);
+            }
+            Command::ToggleSteam => self.toggle_steam(),
+            Command::NextScreen => self.board.display.next_screen(),
+            Command::PreviousScreen => self.board.display.previous_screen(),
+            Command::SetTheme(theme) => {
+                if let Err(e) = self.set_theme(theme) {
+                    log::error!("Failed to set theme: {:?}", e);
+                }
+            }
+        }
+    }
+
+    // Applied to the ring straight away, unlike the rest of the config which needs a reboot
+    pub fn set_theme(&self, theme: Theme) -> anyhow::Result<()> {
+        theme.validate().map_err(anyhow::Error::msg)?;
+        let mut config = self.config.write().unwrap();
+        config.indicator.theme = theme;
+        config.save()?;
+        self.board.indicator.set_theme(theme);
+        self.board.onboard_rgb.set_theme(theme);
+        Ok(())
+    }
+
+    // With a faulted sensor nothing may start the pump or heat the boiler, only turn them off
+    pub fn in_error_state(&self) -> bool {
+        matches!(
+            *self.system_state.lock().unwrap(),
+            SystemState::Error(_) | SystemState::Panic(_)
+        )
+    }
+
+    fn refused_in_error(&self, command: Command) -> bool {
+        let starts_something = match command {
+            Command::PowerOn
+            | Command::SetTemperature(_)
+            | Command::SetPressure(_)
+            | Command::StartShot => true,
+            Command::ToggleSteam => !*self.steam.read().unwrap(),
+            _ => false,
+        };
+        starts_something && self.in_error_state()
+    }
+
+    // False if the operational state refused it, in which case the command shouldn't go ahead
+    fn transition(&self, transition: OperationalTransitions) -> bool {
+        if let Err(e) = self
+            .operational_state
+            .lock()
+            .unwrap()
+            .transition(transition)
+        {
+            log::warn!("Command ignored by the operational state: {:?}", e);
+            return false;
+        }
+        true
+    }
+
+    // Takes a fresh reading first so it blocks for a moment, Some(volume) if there isn't enough to brew.
+    // Without a working level sensor there's nothing to go on, so it doesn't hold up the shot
+    pub fn water_too_low(&self) -> Option<Milliliters> {
+        self.board.level_sensor.send_message(LevelMessage::DoRead);
+        std::thread::sleep(LEVEL_READ_TIME);
+        if !self.board.sensors.level.is_valid() {
+            return None;
+        }
+        let config = self.config.read().unwrap().level_sensor;
+        let volume = config.tank.volume(self.board.sensors.level.get());
+        (volume < config.low_level_threshold).then_some(volume)
+    }
+
+    fn cycle_drink(&self) {
+        let menu = self.menu.read().unwrap();
+        let mut selected = self.selected_drink.write().unwrap();
+        let next = match *selected {
+            Some(current) => menu.range(current + 1..).next(),
+            None => None,
+        }
+        .or_else(|| menu.iter().next());
+
+        *selected = next.map(|(number, _)| *number);
+        self.board
+            .display
+            .set_drink(next.map(|(_, name)| name.to_string()));
+        match next {
+            Some((_, name)) => self.report_info_event(module_path!(), format!("Selected {}", name)),
+            None => log::warn!("No drinks to select"),
+        }
+    }
+
+    // The selected drink if there is one, otherwise the favourite shot
+    fn shot_target(&self) -> (Temperature, Bar, Grams) {
+        let favourite = self.config.read().unwrap().switches.favourite;
+        let fallback = (BREW_TEMPERATURE, favourite.pressure, favourite.weight);
+
+        #[cfg(feature = "sdcard")]
+        {
+            let Some(number) = *self.selected_drink.read().unwrap() else {
+                return fallback;
+            };
+            let menu = self.menu.read().unwrap();
+            let Some(name) = menu.get(&number) else {
+                return fallback;
+            };
+            match Drink::load_drink(name, &menu) {
+                Ok(drink) => {
+                    if let (Some(profile), Some(weight)) =
+                        (drink.shot.profile.first(), drink.shot.weight)
+                    {
+                        return (profile.degrees, profile.pressure, weight);
+                    }
+                    log::warn!("{} is not a yield based shot, brewing the favourite", name);
+                }
+                Err(e) => log::error!("Failed to load {}: {:?}", name, e),
+            }
+        }
+
+        fallback
+    }
+
+    fn start_shot(&self) {
+        if let Some(volume) = self.water_too_low() {
+            self.report_warn_event(
+                module_path!(),
+                format!("Water level too low to brew: {:.0}mL", volume),
+            );
+            return;
+        }
+        let (temperature, pressure, weight) = self.shot_target();
+        if !self.transition(OperationalTransitions::StartBrewing(Some(weight))) {
+            return;
+        }
+        self.report_info_event(
+            module_path!(),
+            format!("Starting shot: {:.1}g at {:.1} bar", weight, pressure),
+        );
+        *self.steam.write().unwrap() = false;
+        self.set_temperature(temperature);
+        self.board.scale.start_brew();
+        self.board.pump.turn_on_for_yield(pressure, weight);
+    }
+
+    fn toggle_steam(&self) {
+        let mut steam = self.steam.write().unwrap();
+        if !self.transition(if *steam {
+            OperationalTransitions::Stop
+        } else {
+            OperationalTransitions::StartSteaming
+        }) {
+            return;
+        }
+        *steam = !*steam;
+        let mode = if *steam {
+            self.board.pump.turn_off();
+            BoilerMode::BangBang {
+                upper_threshold: 140.0,
+                lower_threshold: 120.0,
+            }
+        } else {
+            BoilerMode::Mpc {
+                target: BREW_TEMPERATURE,
+            }
+        };
+        self.board.boiler.send_message(BoilerMessage::SetMode(mode));
+        self.report_info_event(
+            module_path!(),
+            format!("Steam {}", if *steam { "on" } else { "off" }),
+        );
+    }
 }
 
 #[macro_export]
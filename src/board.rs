@@ -1,61 +1,97 @@
+use crate::board_profile::{pin, Profile};
 #[cfg(feature = "sdcard")]
 use crate::components::sd_card::SdCard;
-use crate::components::{boiler::Boiler, pump::Pump};
-use crate::config::Config;
-use crate::gpio::{adc::Adc, switch::Switches};
+use crate::components::{boiler::Boiler, descale::Descale, pump::Pump, valve::Valve};
+use crate::config::{AdcBackend, Boiler as BoilerConfig, BoilerProbe, Config};
+use crate::display::driver::Display;
+use crate::gpio::{
+    adc::{Adc, AdcSource, OnChipAdc},
+    ads1115::Ads1115,
+    button::Buttons,
+    switch::Switches,
+};
+use crate::indicator::onboard::{Network, Wifi};
 use crate::indicator::ring::{Ring, State as IndicatorState};
+use crate::schemas::command::Command;
+use crate::schemas::event::EventBuffer;
 use crate::schemas::status::Device as DeviceReport;
 use crate::sensors::a02yyuw::A02yyuw;
-use crate::sensors::pressure::SeeedWaterPressureSensor;
+use crate::sensors::ambient::{Discovered as DiscoveredProbe, Temperatures as ProbeTemperatures};
+use crate::sensors::health::{Quality, SensorHealth};
+use crate::sensors::max31865::{Max31865, Max31865Driver};
+use crate::sensors::pressure::Transducer;
 use crate::sensors::pt100::Pt100;
+use crate::sensors::reading::Sensor;
+use crate::sensors::registry::Registry;
 use crate::sensors::scale::{Interface as LoadCell, Scale};
+use crate::sensors::tank::Geometry as TankGeometry;
 use crate::sensors::traits::TemperatureProbe;
 use crate::state_machines::{
     operational_fsm::{OperationalState, Transitions},
     ArcMutexState,
 };
+use crate::types::Bar;
 use core::convert::TryInto;
+use embedded_hal_bus::i2c::MutexDevice;
 use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
 use esp_idf_hal::adc::oneshot::config::Calibration;
-use esp_idf_hal::adc::{
-    attenuation,
-    oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
-};
+use esp_idf_hal::adc::{attenuation, oneshot::config::AdcChannelConfig};
+use esp_idf_hal::i2c::{I2cConfig, I2cDriver};
+use esp_idf_hal::spi::{config::Config as SpiConfig, SpiDeviceDriver, SpiDriverConfig};
+use esp_idf_hal::units::FromValueType;
 use esp_idf_svc::hal::task::block_on;
 use esp_idf_svc::hal::{delay::FreeRtos, prelude::Peripherals};
+use esp_idf_svc::sntp::EspSntp;
 use esp_idf_svc::timer::EspTaskTimerService;
-use esp_idf_svc::wifi::WifiDeviceId;
+use esp_idf_svc::wifi::{WifiDeviceId, WifiEvent};
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     nvs::EspDefaultNvsPartition,
     wifi::{AsyncWifi, EspWifi},
 };
-use std::sync::{Arc, Mutex, RwLock};
+use std::ops::RangeInclusive;
+use std::sync::{mpsc::Sender, Arc, Mutex, RwLock};
 use std::thread;
 
+const BOILER_PROBE_RANGE: RangeInclusive<f32> = -10.0..=200.0;
+const PRESSURE_PROBE_RANGE: RangeInclusive<f32> = -1.0..=20.0;
+
 #[derive(Clone)]
 pub struct Board {
     pub indicator: Ring,
-    #[allow(dead_code)]
     pub onboard_rgb: Ring,
-    pub temperature: Arc<RwLock<f32>>,
-    pub ambient_temperature: Arc<RwLock<f32>>,
+    pub network: Network,
+    pub display: Display,
+    pub sensors: Registry,
+    pub temperature_raw: Arc<RwLock<f64>>,
+    pub temperature_probe: Arc<RwLock<Box<dyn TemperatureProbe + Send + Sync>>>,
+    pub temperature_probes: Arc<RwLock<ProbeTemperatures>>,
+    pub discovered_probes: Arc<RwLock<Vec<DiscoveredProbe>>>,
     pub scale: LoadCell,
     pub switches: Switches,
-    pub pressure: Arc<RwLock<f32>>,
+    pub pressure_voltage: Arc<RwLock<f64>>,
+    pub pressure_sensor: Arc<RwLock<Transducer>>,
     pub pump: Pump,
     pub boiler: Boiler,
     pub level_sensor: A02yyuw,
+    pub health: SensorHealth,
+    pub descale: Descale,
     pub mac: Arc<String>,
 }
 
 impl Board {
-    pub fn new(operational_state: Arc<Mutex<OperationalState>>, config: &mut Config) -> Self {
+    pub fn new(
+        operational_state: Arc<Mutex<OperationalState>>,
+        events: Arc<Mutex<EventBuffer>>,
+        commands: Sender<Command>,
+        config: &mut Config,
+    ) -> Self {
         operational_state
             .transition(Transitions::StartingUpStage("Board Setup".to_string()))
             .expect("Failed to set operational state");
 
         let peripherals = Peripherals::take().expect("You're probably calling this twice!");
+        let profile = Self::board_profile(config);
 
         log::info!("Setting up indicator");
         operational_state
@@ -64,13 +100,14 @@ impl Board {
 
         let onboard_led = Ring::new(
             peripherals.rmt.channel0,
-            peripherals.pins.gpio48,
+            pin(profile.onboard_rgb),
             config.indicator.refresh_interval,
             1,
+            config.indicator.theme,
         );
-        onboard_led.set_state(IndicatorState::Heartbeat);
+        onboard_led.set_state(IndicatorState::Busy);
 
-        let led_pin = peripherals.pins.gpio21;
+        let led_pin = pin(profile.indicator);
         let channel = peripherals.rmt.channel1;
 
         let ring = Ring::new(
@@ -78,6 +115,7 @@ impl Board {
             led_pin,
             config.indicator.refresh_interval,
             config.indicator.led_count,
+            config.indicator.theme,
         );
         ring.set_state(IndicatorState::Busy);
 
@@ -85,16 +123,34 @@ impl Board {
             .transition(Transitions::StartingUpStage("Input Setup".to_string()))
             .expect("Failed to set operational state");
 
-        let ambient_probe = crate::sensors::ambient::AmbientSensor::new(peripherals.pins.gpio3);
+        let sensors = Registry::new();
+        let health = SensorHealth::new(&sensors);
+        let (temperature_probes, discovered_probes) = match profile.ambient {
+            Some(gpio) => {
+                let ambient_probe = crate::sensors::ambient::AmbientSensor::new(
+                    pin(gpio),
+                    config.temperature_probes.assignments,
+                    sensors.ambient.clone(),
+                );
+                (ambient_probe.probes, ambient_probe.discovered)
+            }
+            None => {
+                log::info!("Board has no one-wire bus, skipping temperature probes");
+                (
+                    Arc::new(RwLock::new(ProbeTemperatures::default())),
+                    Arc::new(RwLock::new(Vec::new())),
+                )
+            }
+        };
 
         #[cfg(feature = "sdcard")]
-        {
+        if let Some(spi) = profile.sd_card {
             let sd_card = SdCard::new(
                 peripherals.spi2,
-                peripherals.pins.gpio12,
-                peripherals.pins.gpio13,
-                peripherals.pins.gpio11,
-                Some(peripherals.pins.gpio10),
+                pin(spi.sclk),
+                pin(spi.sdo),
+                pin(spi.sdi),
+                Some(pin(spi.cs)),
             );
 
             if let Ok(card) = sd_card {
@@ -103,10 +159,22 @@ impl Board {
             } else {
                 log::error!("Failed to initialize SD Card");
             }
+        } else {
+            log::info!("Board has no SD card slot");
         }
 
         log::info!("Setting up wifi");
         let sys_loop = EspSystemEventLoop::take().expect("Unable to take sysloop");
+        let network = Network::default();
+        let wifi_network = network.clone();
+        match sys_loop.subscribe::<WifiEvent, _>(move |event| match event {
+            WifiEvent::StaConnected { .. } => wifi_network.set_wifi(Wifi::Connected),
+            WifiEvent::StaDisconnected { .. } => wifi_network.set_wifi(Wifi::Disconnected),
+            _ => {}
+        }) {
+            Ok(subscription) => core::mem::forget(subscription),
+            Err(e) => log::error!("Failed to subscribe to wifi events: {:?}", e),
+        }
         let timer_service = EspTaskTimerService::new().expect("Failed to create timer service");
         let nvs = EspDefaultNvsPartition::take().expect("Failed to take nvs partition");
         config.nvs = Some(nvs.clone());
@@ -136,100 +204,214 @@ impl Board {
                     .expect("Failed to get IP info");
                 log::info!("Wifi DHCP info: {:?}", ip_info);
             }
-            Err(e) => log::error!("Failed to connect wifi: {:?}", e),
+            Err(e) => {
+                log::error!("Failed to connect wifi: {:?}", e);
+                network.set_wifi(Wifi::Disconnected);
+            }
         }
         core::mem::forget(wifi);
 
+        match EspSntp::new_default() {
+            Ok(sntp) => core::mem::forget(sntp),
+            Err(e) => log::error!("Failed to start SNTP: {:?}", e),
+        }
+
         log::info!("Setting up switches");
         let switches = Switches::new(
-            peripherals.pins.gpio6,
-            peripherals.pins.gpio7,
-            peripherals.pins.gpio15,
+            pin(profile.switches.brew),
+            pin(profile.switches.hot_water),
+            pin(profile.switches.steam),
+            profile.switches.active_low,
+            events.clone(),
+            config.switches,
         );
 
+        if let Some(buttons) = profile.buttons {
+            log::info!("Setting up buttons");
+            Buttons::start(
+                buttons.pins.map(|gpio| gpio.map(pin)),
+                buttons.active_low,
+                commands.clone(),
+                config.buttons,
+            );
+        }
+
         log::info!("Setting up ADCs");
-        let pressure_probe = Arc::new(RwLock::new(0.0));
-        let temperature = Arc::new(RwLock::new(f32::default()));
+        let temperature_sensor = sensors.temperature.clone();
+        let pressure_sensor_reading = sensors.pressure.clone();
+        let temperature_raw = Arc::new(RwLock::new(0.0));
+        let temperature_raw_clone = temperature_raw.clone();
+
+        let pressure_voltage = Arc::new(RwLock::new(0.0));
+        let pressure_voltage_clone = pressure_voltage.clone();
+        let pressure_sensor = Arc::new(RwLock::new(config.pressure_sensor));
+        let pressure_sensor_clone = pressure_sensor.clone();
+        let temperature_probe =
+            Arc::new(RwLock::new(Self::create_temperature_probe(&config.boiler)));
         #[cfg(not(feature = "simulate"))]
-        let temperature_clone = temperature.clone();
-        let pressure_probe_clone = pressure_probe.clone();
+        let temperature_probe_clone = temperature_probe.clone();
+        let boiler_probe = config.boiler.probe;
+        let max31865_pins = profile.max31865;
+
+        let loadcell = match profile.scale {
+            Some(hx711) => {
+                log::info!("Setting up scale");
+                Scale::start(
+                    pin(hx711.sck),
+                    pin(hx711.dt),
+                    &config.load_cell,
+                    sensors.weight.clone(),
+                    events.clone(),
+                )
+                .unwrap()
+            }
+            None => {
+                log::info!("Board has no scale");
+                LoadCell::absent()
+            }
+        };
 
-        let seed_pressure_probe = SeeedWaterPressureSensor::default();
-        let pt100 = Pt100 {
-            calibration: config.boiler.pt100_calibration_factor,
+        // The ADS1115 and the display share the bus, each with its own device on it
+        let needs_i2c =
+            matches!(config.adc.backend, AdcBackend::Ads1115(_)) || profile.display.is_some();
+        let i2c_bus: Option<&'static Mutex<I2cDriver<'static>>> = match profile.i2c {
+            Some(i2c_pins) if needs_i2c => {
+                let i2c = I2cDriver::new(
+                    peripherals.i2c0,
+                    pin(i2c_pins.sda),
+                    pin(i2c_pins.scl),
+                    &I2cConfig::new().baudrate(400.kHz().into()),
+                )
+                .expect("Failed to create I2C driver");
+                Some(Box::leak(Box::new(Mutex::new(i2c))))
+            }
+            _ => None,
         };
 
-        log::info!("Setting up scale");
-        let dt = peripherals.pins.gpio36;
-        let sck = peripherals.pins.gpio35;
-        let loadcell = Scale::start(sck, dt, &config.load_cell).unwrap();
+        let display = match (profile.display, i2c_bus) {
+            (Some(ssd1306), Some(i2c_bus)) => {
+                log::info!("Setting up display at {:#04x}", ssd1306.address);
+                Display::start(
+                    MutexDevice::new(i2c_bus),
+                    ssd1306.address,
+                    sensors.clone(),
+                    loadcell.shot_started.clone(),
+                    config.display,
+                )
+            }
+            _ => {
+                log::info!("Board has no display");
+                Display::absent()
+            }
+        };
 
-        log::info!("Setting up level sensor");
-        let tx = peripherals.pins.gpio43;
-        let rx = peripherals.pins.gpio44;
-        let uart = peripherals.uart0;
-        let level_sensor = A02yyuw::new(uart, rx, tx);
-        log::info!("Starting level sensor");
+        let level_sensor = match profile.level_sensor {
+            Some(uart) => {
+                log::info!("Setting up level sensor");
+                A02yyuw::new(
+                    peripherals.uart0,
+                    pin(uart.rx),
+                    pin(uart.tx),
+                    sensors.level.clone(),
+                    events,
+                    config.level_sensor,
+                )
+            }
+            None => {
+                log::info!("Board has no level sensor");
+                A02yyuw::absent(config.level_sensor)
+            }
+        };
 
         let sensor_killswitch = Arc::new(Mutex::new(false));
         let sensor_killswitch_clone = sensor_killswitch.clone();
-        let adc_polling_interval = config.adc.polling_interval;
-        let adc_window = config.adc.window;
+        let adc_config = config.adc;
         thread::Builder::new()
             .name("sensor".to_string())
             .spawn(move || {
-                let adc = AdcDriver::new(peripherals.adc1).expect("Failed to create ADC driver");
-                let channel_config = AdcChannelConfig {
-                    attenuation: attenuation::DB_11,
-                    calibration: Calibration::None,
-                    ..Default::default()
+                let source: Box<dyn AdcSource> = match adc_config.backend {
+                    AdcBackend::OnChip => {
+                        log::info!("Using on-chip ADC");
+                        let channel_config = AdcChannelConfig {
+                            attenuation: attenuation::DB_11,
+                            calibration: Calibration::None,
+                            ..Default::default()
+                        };
+                        Box::new(
+                            OnChipAdc::new(
+                                peripherals.adc1,
+                                peripherals.pins.gpio4,
+                                peripherals.pins.gpio5,
+                                &channel_config,
+                            )
+                            .expect("Failed to create ADC driver"),
+                        )
+                    }
+                    AdcBackend::Ads1115(ads1115_config) => {
+                        log::info!("Using ADS1115 at {:#04x}", ads1115_config.address);
+                        let i2c_bus = i2c_bus.expect("Board profile has no I2C bus");
+                        Box::new(Ads1115::new(MutexDevice::new(i2c_bus), ads1115_config))
+                    }
+                };
+                let mut adc = Adc::new(source, adc_config.polling_interval, adc_config.window);
+
+                let mut max31865 = match boiler_probe {
+                    BoilerProbe::AnalogPt100 => None,
+                    BoilerProbe::Max31865(max31865_config) => {
+                        log::info!("Using MAX31865 for the boiler probe");
+                        let spi_pins = max31865_pins.expect("Board profile has no MAX31865");
+                        let spi = SpiDeviceDriver::new_single(
+                            peripherals.spi3,
+                            pin(spi_pins.sclk),
+                            pin(spi_pins.sdo),
+                            Some(pin(spi_pins.sdi)),
+                            Some(pin(spi_pins.cs)),
+                            &SpiDriverConfig::new(),
+                            &SpiConfig::new()
+                                .baudrate(1.MHz().into())
+                                .data_mode(embedded_hal::spi::MODE_1),
+                        )
+                        .expect("Failed to create SPI driver");
+                        Some(
+                            Max31865Driver::new(spi, max31865_config)
+                                .expect("Failed to set up MAX31865"),
+                        )
+                    }
                 };
-
-                let temperature_probe =
-                    AdcChannelDriver::new(&adc, peripherals.pins.gpio4, &channel_config)
-                        .expect("Failed to create ADC channel temperature");
-                let pressure_probe =
-                    AdcChannelDriver::new(&adc, peripherals.pins.gpio5, &channel_config)
-                        .expect("Failed to create ADC channel pressure");
-                let mut adc = Adc::new(
-                    temperature_probe,
-                    pressure_probe,
-                    adc_polling_interval,
-                    adc_window,
-                );
 
                 loop {
                     if *sensor_killswitch_clone.lock().unwrap() {
                         log::info!("Sensor thread killed");
                         return;
                     }
-                    if let Some((temperature, pressure)) = adc.read() {
-                        let degrees = match pt100.convert_voltage_to_degrees(temperature / 1000.0) {
-                            Ok(degrees) => degrees,
-                            Err(e) => {
-                                log::error!("Failed to convert voltage to degrees: {:?}", e);
-                                999.0
-                            }
-                        };
-                        #[cfg(not(feature = "simulate"))]
-                        {
-                            *temperature_clone.write().unwrap() = degrees;
+                    // The MAX31865 converts on its own, so it doesn't wait on the ADC
+                    let reading = adc.read();
+                    let raw = match max31865.as_mut() {
+                        Some(max31865) => max31865.read(),
+                        None => reading.map(|(temperature, _)| Ok(temperature / 1000.0)),
+                    };
+                    match raw {
+                        Some(Ok(raw)) => {
+                            *temperature_raw_clone.write().unwrap() = raw;
+                            #[cfg(not(feature = "simulate"))]
+                            Self::update_temperature(
+                                temperature_probe_clone.read().unwrap().as_ref(),
+                                raw,
+                                &temperature_sensor,
+                            );
                         }
-                        #[cfg(feature = "simulate")]
-                        {
-                            let _ = degrees;
-                        }
-                        use crate::sensors::traits::PressureProbe;
-                        let pressure = match seed_pressure_probe
-                            .convert_voltage_to_pressure(pressure / 1000.0)
-                        {
-                            Ok(pressure) => pressure,
-                            Err(e) => {
-                                log::error!("Failed to convert voltage to pressure: {:?}", e);
-                                continue;
-                            }
-                        };
-                        *pressure_probe_clone.write().unwrap() = pressure;
+                        Some(Err(fault)) => temperature_sensor.fault(fault),
+                        None => {}
+                    }
+
+                    if let Some((_, pressure)) = reading {
+                        let pressure = pressure / 1000.0;
+                        *pressure_voltage_clone.write().unwrap() = pressure;
+                        Self::update_pressure(
+                            &pressure_sensor_clone.read().unwrap(),
+                            pressure,
+                            &pressure_sensor_reading,
+                        );
                     }
 
                     FreeRtos::delay_ms(10);
@@ -243,36 +425,136 @@ impl Board {
         log::info!("Setting up outputs");
 
         let boiler = Boiler::new(
-            ambient_probe.temperature.clone(),
-            temperature.clone(),
-            peripherals.pins.gpio1,
+            sensors.ambient.clone(),
+            sensors.temperature.clone(),
+            pin(profile.element),
             config.boiler,
         );
+        let valve = Valve::new(
+            pin(profile.solenoid.gpio),
+            profile.solenoid.inverted,
+            config.nvs.clone(),
+            config.pump.valve,
+        );
         let pump = Pump::new(
-            peripherals.pins.gpio42,
-            peripherals.pins.gpio2,
-            pressure_probe.clone(),
-            loadcell.weight.clone(),
+            pin(profile.pump),
+            valve,
+            &sensors,
+            loadcell.cup_removed.clone(),
+            level_sensor.clone(),
+            commands,
             config.pump,
         );
 
+        let descale = Descale::new(
+            pump.clone(),
+            boiler.clone(),
+            level_sensor.clone(),
+            sensors.level.clone(),
+            sensors.temperature.clone(),
+            operational_state.clone(),
+            config.nvs.clone(),
+            config.descale,
+        );
+
         log::info!("Board setup complete");
 
         Board {
             indicator: ring,
             onboard_rgb: onboard_led,
-            temperature,
-            ambient_temperature: ambient_probe.temperature,
+            network,
+            display,
+            sensors,
+            temperature_raw,
+            temperature_probe,
+            temperature_probes,
+            discovered_probes,
             scale: loadcell,
             switches,
             pump,
             boiler,
-            pressure: pressure_probe,
+            pressure_voltage,
+            pressure_sensor,
             level_sensor,
+            health,
+            descale,
             mac: Arc::new(mac),
         }
     }
 
+    // A stored profile wins over the one built in, as long as it makes sense for this config
+    fn board_profile(config: &Config) -> Profile {
+        if let Some(profile) = config.board {
+            match profile.validate(config) {
+                Ok(()) => return profile,
+                Err(e) => log::error!("Stored board profile is invalid: {}", e),
+            }
+        }
+
+        Profile::BUILTIN
+            .validate(config)
+            .expect("Built-in board profile does not suit the config");
+        Profile::BUILTIN
+    }
+
+    pub fn create_temperature_probe(
+        config: &BoilerConfig,
+    ) -> Box<dyn TemperatureProbe + Send + Sync> {
+        match config.probe {
+            BoilerProbe::AnalogPt100 => Box::new(Pt100::new(
+                config.pt100_calibration_factor,
+                config.pt100_calibration,
+            )),
+            BoilerProbe::Max31865(max31865_config) => {
+                Box::new(Max31865::new(max31865_config, config.pt100_calibration))
+            }
+        }
+    }
+
+    #[cfg(not(feature = "simulate"))]
+    fn update_temperature(
+        probe: &dyn TemperatureProbe,
+        raw: f64,
+        temperature: &Sensor<crate::types::Temperature>,
+    ) {
+        match probe.diagnose(raw) {
+            Quality::Good => match probe.convert_voltage_to_degrees(raw) {
+                Ok(degrees) if BOILER_PROBE_RANGE.contains(&degrees) => {
+                    temperature.update(degrees);
+                }
+                Ok(degrees) => {
+                    log::error!("Boiler temperature out of range: {}", degrees);
+                    temperature.fault(Quality::OutOfRange);
+                }
+                Err(e) => {
+                    log::error!("Failed to convert voltage to degrees: {:?}", e);
+                    temperature.fault(Quality::OutOfRange);
+                }
+            },
+            fault => temperature.fault(fault),
+        }
+    }
+
+    fn update_pressure(probe: &Transducer, voltage: f64, pressure: &Sensor<Bar>) {
+        use crate::sensors::traits::PressureProbe;
+        match probe.diagnose(voltage) {
+            Quality::Good => match probe.convert_voltage_to_pressure(voltage) {
+                Ok(bar) if PRESSURE_PROBE_RANGE.contains(&bar) => {
+                    pressure.update(bar);
+                }
+                Ok(bar) => {
+                    log::error!("Pressure out of range: {}", bar);
+                    pressure.fault(Quality::OutOfRange);
+                }
+                Err(e) => {
+                    log::error!("Failed to convert voltage to pressure: {:?}", e);
+                    pressure.fault(Quality::OutOfRange);
+                }
+            },
+            fault => pressure.fault(fault),
+        }
+    }
+
     async fn connect_wifi(wifi: &mut AsyncWifi<EspWifi<'static>>) -> anyhow::Result<()> {
         use dotenv_codegen::dotenv;
         let wifi_configuration = Configuration::Client(ClientConfiguration {
@@ -300,14 +582,18 @@ impl Board {
         Ok(())
     }
 
-    pub fn generate_report(&self) -> DeviceReport {
+    pub fn generate_report(&self, tank: &TankGeometry) -> DeviceReport {
+        let readings = self.sensors.readings();
         DeviceReport {
-            temperature: *self.temperature.read().unwrap(),
-            pressure: *self.pressure.read().unwrap(),
-            weight: *self.scale.weight.read().unwrap(),
-            ambient: *self.ambient_temperature.read().unwrap(),
-            level: *self.level_sensor.distance.read().unwrap(),
-            power: 0.0,
+            temperature: readings.temperature,
+            pressure: readings.pressure,
+            weight: readings.weight,
+            ambient: readings.ambient,
+            level: readings.level,
+            probes: *self.temperature_probes.read().unwrap(),
+            power: self.boiler.get_power(),
+            volume: tank.volume(readings.level.value),
+            fill: tank.percentage(readings.level.value),
             switches: self.switches.get_report(),
         }
     }
@@ -0,0 +1,215 @@
+use crate::config::{AdcBackend, BoilerProbe, Config};
+use crate::gpio::button::MAX_BUTTONS;
+use esp_idf_hal::gpio::AnyIOPin;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub type Gpio = u8;
+
+// The on-chip ADC channels are typed at compile time by the HAL, so the boiler probe is on
+// GPIO4 and the pressure sensor on GPIO5. The sensor thread takes both pins whichever ADC
+// is configured, so no profile may use them for anything else.
+pub const ON_CHIP_ADC_PINS: [Gpio; 2] = [4, 5];
+
+// Taken by the flash and quad PSRAM on every module, octal PSRAM also takes 33 to 37
+const FLASH_PINS: RangeInclusive<Gpio> = 26..=32;
+const OCTAL_PSRAM_PINS: RangeInclusive<Gpio> = 33..=37;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Spi {
+    pub sclk: Gpio,
+    pub sdo: Gpio,
+    pub sdi: Gpio,
+    pub cs: Gpio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct I2c {
+    pub sda: Gpio,
+    pub scl: Gpio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Uart {
+    pub tx: Gpio,
+    pub rx: Gpio,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Hx711 {
+    pub sck: Gpio,
+    pub dt: Gpio,
+}
+
+// Shares the I2C bus with the ADS1115
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Ssd1306 {
+    pub address: u8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Output {
+    pub gpio: Gpio,
+    pub inverted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Switches {
+    pub brew: Gpio,
+    pub hot_water: Gpio,
+    pub steam: Gpio,
+    pub active_low: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Buttons {
+    pub pins: [Option<Gpio>; MAX_BUTTONS],
+    pub active_low: bool,
+}
+
+// Which pins every peripheral is wired to on a given PCB revision. Peripherals that
+// are optional on the board are `None` when they aren't fitted.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Profile {
+    pub onboard_rgb: Gpio,
+    pub indicator: Gpio,
+    pub element: Gpio,
+    pub pump: Gpio,
+    pub solenoid: Output,
+    pub switches: Switches,
+    pub buttons: Option<Buttons>,
+    pub i2c: Option<I2c>,
+    pub max31865: Option<Spi>,
+    pub sd_card: Option<Spi>,
+    pub scale: Option<Hx711>,
+    pub level_sensor: Option<Uart>,
+    pub ambient: Option<Gpio>,
+    pub display: Option<Ssd1306>,
+}
+
+impl Profile {
+    pub const REV1: Profile = Profile {
+        onboard_rgb: 48,
+        indicator: 21,
+        element: 1,
+        pump: 42,
+        solenoid: Output {
+            gpio: 2,
+            inverted: false,
+        },
+        switches: Switches {
+            brew: 6,
+            hot_water: 7,
+            steam: 15,
+            active_low: true,
+        },
+        buttons: Some(Buttons {
+            pins: [Some(14), Some(16), Some(17), Some(18)],
+            active_low: true,
+        }),
+        i2c: Some(I2c { sda: 8, scl: 9 }),
+        max31865: Some(Spi {
+            sclk: 39,
+            sdo: 40,
+            sdi: 41,
+            cs: 38,
+        }),
+        sd_card: Some(Spi {
+            sclk: 12,
+            sdo: 13,
+            sdi: 11,
+            cs: 10,
+        }),
+        scale: Some(Hx711 { sck: 35, dt: 36 }),
+        level_sensor: Some(Uart { tx: 43, rx: 44 }),
+        ambient: Some(3),
+        display: Some(Ssd1306 { address: 0x3c }),
+    };
+
+    // The controller on its own, without any of the add-on modules fitted
+    #[cfg_attr(not(feature = "board_minimal"), allow(dead_code))]
+    pub const MINIMAL: Profile = Profile {
+        buttons: None,
+        i2c: None,
+        max31865: None,
+        sd_card: None,
+        scale: None,
+        level_sensor: None,
+        ambient: None,
+        display: None,
+        ..Self::REV1
+    };
+
+    #[cfg(not(feature = "board_minimal"))]
+    pub const BUILTIN: Profile = Self::REV1;
+    #[cfg(feature = "board_minimal")]
+    pub const BUILTIN: Profile = Self::MINIMAL;
+
+    fn pins(&self) -> Vec<Gpio> {
+        let mut pins = vec![
+            self.onboard_rgb,
+            self.indicator,
+            self.element,
+            self.pump,
+            self.solenoid.gpio,
+            self.switches.brew,
+            self.switches.hot_water,
+            self.switches.steam,
+        ];
+        if let Some(buttons) = self.buttons {
+            pins.extend(buttons.pins.iter().flatten());
+        }
+        if let Some(i2c) = self.i2c {
+            pins.extend([i2c.sda, i2c.scl]);
+        }
+        for spi in [self.max31865, self.sd_card].iter().flatten() {
+            pins.extend([spi.sclk, spi.sdo, spi.sdi, spi.cs]);
+        }
+        if let Some(scale) = self.scale {
+            pins.extend([scale.sck, scale.dt]);
+        }
+        if let Some(uart) = self.level_sensor {
+            pins.extend([uart.tx, uart.rx]);
+        }
+        pins.extend(self.ambient);
+        pins
+    }
+
+    pub fn validate(&self, config: &Config) -> Result<(), String> {
+        let used = self.pins();
+        if let Some(gpio) = used.iter().find(|gpio| ON_CHIP_ADC_PINS.contains(gpio)) {
+            return Err(format!("GPIO{} is kept for the on-chip ADC", gpio));
+        }
+        if matches!(config.adc.backend, AdcBackend::Ads1115(_)) && self.i2c.is_none() {
+            return Err("The ADS1115 needs an I2C bus".to_string());
+        }
+        if self.display.is_some() && self.i2c.is_none() {
+            return Err("The display needs an I2C bus".to_string());
+        }
+        if matches!(config.boiler.probe, BoilerProbe::Max31865(_)) && self.max31865.is_none() {
+            return Err("The MAX31865 needs an SPI bus".to_string());
+        }
+
+        for (index, gpio) in used.iter().enumerate() {
+            if !matches!(gpio, 0..=21 | 26..=48) {
+                return Err(format!("GPIO{} does not exist on the ESP32-S3", gpio));
+            }
+            if FLASH_PINS.contains(gpio) {
+                return Err(format!("GPIO{} is wired to the SPI flash", gpio));
+            }
+            if cfg!(esp_idf_spiram_mode_oct) && OCTAL_PSRAM_PINS.contains(gpio) {
+                return Err(format!("GPIO{} is wired to the octal PSRAM", gpio));
+            }
+            if used[..index].contains(gpio) {
+                return Err(format!("GPIO{} is assigned more than once", gpio));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn pin(gpio: Gpio) -> AnyIOPin {
+    // Safety: `Board::new` owns `Peripherals` and only hands out pins from a validated
+    // profile, so no GPIO is driven from two places
+    unsafe { AnyIOPin::new(gpio as i32) }
+}
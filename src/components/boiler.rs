@@ -1,7 +1,8 @@
 use crate::config::{self, Boiler as Config};
 use crate::gpio::pwm::PwmBuilder;
 use crate::models::boiler::{BoilerModel, BoilerModelParameters};
-use crate::types::Temperature;
+use crate::sensors::reading::Sensor;
+use crate::types::{Temperature, Watts};
 use esp_idf_svc::hal::delay::FreeRtos;
 use esp_idf_svc::hal::gpio::OutputPin;
 use std::sync::{
@@ -11,6 +12,9 @@ use std::sync::{
 use std::time::{Duration, Instant};
 
 const UPDATE_INTERVAL: u64 = 1000;
+// How close to the target, and for how long, the probe has to be before a shot is worth pulling
+const READY_BAND: Temperature = 0.5;
+const READY_AFTER: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mode {
@@ -41,6 +45,15 @@ impl std::fmt::Display for Mode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Readiness {
+    #[default]
+    Off,
+    // The model's estimate of the time left, None when it can't tell
+    Heating(Option<Duration>),
+    Ready,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Message {
     SetMode(Mode),
@@ -77,6 +90,9 @@ pub type Mailbox = Sender<Message>;
 #[derive(Clone)]
 pub struct Boiler {
     mailbox: Mailbox,
+    readiness: Arc<RwLock<Readiness>>,
+    power: Arc<RwLock<Watts>>,
+    target: Arc<RwLock<Option<Temperature>>>,
 }
 
 impl Boiler {
@@ -84,9 +100,22 @@ impl Boiler {
         self.mailbox.send(message).unwrap();
     }
 
+    pub fn get_readiness(&self) -> Readiness {
+        *self.readiness.read().unwrap()
+    }
+
+    pub fn get_power(&self) -> Watts {
+        *self.power.read().unwrap()
+    }
+
+    // What the controller is currently aiming for, None when it isn't holding a temperature
+    pub fn get_target(&self) -> Option<Temperature> {
+        *self.target.read().unwrap()
+    }
+
     pub fn new<PE>(
-        ambient_probe: Arc<RwLock<Temperature>>,
-        temperature_probe: Arc<RwLock<Temperature>>,
+        ambient_probe: Sensor<Temperature>,
+        temperature_probe: Sensor<Temperature>,
         element_pin: PE,
         config: Config,
     ) -> Self
@@ -103,6 +132,12 @@ impl Boiler {
         #[cfg(feature = "simulate")]
         let boiler_simulator = crate::models::boiler::BoilerModel::new(Some(25.0));
         let mut next_iteration = Instant::now() + Duration::from_millis(UPDATE_INTERVAL);
+        let readiness = Arc::new(RwLock::new(Readiness::Off));
+        let my_readiness = readiness.clone();
+        let power = Arc::new(RwLock::new(0.0));
+        let my_power = power.clone();
+        let target = Arc::new(RwLock::new(None));
+        let my_target = target.clone();
 
         std::thread::Builder::new()
             .name("Boiler".to_string())
@@ -110,6 +145,8 @@ impl Boiler {
                 let mut my_mode = Mode::Off;
                 let mut duty_cycle = 0.0;
                 let mut my_boiler_model = model;
+                let mut probe_fault = false;
+                let mut in_band_since: Option<Instant> = None;
                 #[cfg(feature = "simulate")]
                 let mut boiler_simulator = boiler_simulator;
                 #[cfg(feature = "simulate")]
@@ -121,6 +158,14 @@ impl Boiler {
                     while let Ok(message) = rx.try_recv() {
                         message.handle(&mut my_boiler_model, &mut my_mode);
                     }
+                    *my_target.write().unwrap() = match my_mode {
+                        Mode::Mpc { target } => Some(target),
+                        Mode::BangBang {
+                            upper_threshold,
+                            lower_threshold,
+                        } => Some((upper_threshold + lower_threshold) / 2.0),
+                        Mode::Off | Mode::Transparent { .. } => None,
+                    };
 
                     duty_cycle = match my_mode {
                         Mode::Off => 0.0,
@@ -135,7 +180,7 @@ impl Boiler {
                             next_iteration += Duration::from_secs_f32(
                                 UPDATE_INTERVAL as f32 * config::TIME_DILATION_FACTOR / 1000.0,
                             );
-                            let probe_temperature = *temperature_probe.read().unwrap();
+                            let probe_temperature = temperature_probe.get();
                             if probe_temperature >= upper_threshold {
                                 0.0
                             } else if probe_temperature <= lower_threshold {
@@ -148,10 +193,10 @@ impl Boiler {
                             if next_iteration > Instant::now() {
                                 continue;
                             }
-                            let probe_temperature = *temperature_probe.read().unwrap();
+                            let probe_temperature = temperature_probe.get();
                             let power = my_boiler_model.control(
                                 probe_temperature,
-                                *ambient_probe.read().unwrap(),
+                                ambient_probe.get(),
                                 target,
                                 Duration::from_millis(UPDATE_INTERVAL),
                             );
@@ -164,14 +209,55 @@ impl Boiler {
                         }
                     };
 
+                    // Never heat blind
+                    if temperature_probe.is_valid() {
+                        probe_fault = false;
+                    } else if duty_cycle > 0.0 {
+                        if !probe_fault {
+                            log::error!("No valid boiler probe reading, element disabled");
+                            probe_fault = true;
+                        }
+                        duty_cycle = 0.0;
+                    }
+
+                    let probe_temperature = temperature_probe.get();
+                    let ready = match my_mode {
+                        Mode::Off => Readiness::Off,
+                        Mode::Transparent { .. } => Readiness::Heating(None),
+                        Mode::BangBang {
+                            lower_threshold, ..
+                        } if probe_temperature >= lower_threshold => Readiness::Ready,
+                        Mode::BangBang { .. } => Readiness::Heating(None),
+                        Mode::Mpc { target } => {
+                            if (probe_temperature - target).abs() > READY_BAND {
+                                in_band_since = None;
+                            } else if in_band_since.is_none() {
+                                in_band_since = Some(Instant::now());
+                            }
+                            match in_band_since.map(|since| since.elapsed()) {
+                                Some(elapsed) if elapsed >= READY_AFTER => Readiness::Ready,
+                                Some(elapsed) => {
+                                    Readiness::Heating(Some(READY_AFTER.saturating_sub(elapsed)))
+                                }
+                                None => Readiness::Heating(
+                                    my_boiler_model
+                                        .time_to_setpoint(target, ambient_probe.get())
+                                        .map(|time| time + READY_AFTER),
+                                ),
+                            }
+                        }
+                    };
+                    *my_readiness.write().unwrap() = ready;
+
                     #[cfg(feature = "simulate")]
                     {
                         let (_, probe) = boiler_simulator.update(
                             duty_cycle * boiler_simulator.max_power,
                             Duration::from_millis(UPDATE_INTERVAL),
                         );
-                        *temperature_probe.write().unwrap() = probe;
+                        temperature_probe.update(probe);
                     }
+                    *my_power.write().unwrap() = duty_cycle * config.power;
                     {
                         element.set_duty_cycle(duty_cycle);
                         element.tick();
@@ -181,6 +267,11 @@ impl Boiler {
             })
             .expect("Failed to spawn output thread");
 
-        Self { mailbox }
+        Self {
+            mailbox,
+            readiness,
+            power,
+            target,
+        }
     }
 }
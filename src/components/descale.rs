@@ -0,0 +1,374 @@
+use crate::components::boiler::{Boiler, Message as BoilerMessage, Mode as BoilerMode};
+use crate::components::pump::Pump;
+use crate::config::Descale as Config;
+use crate::maintenance::Maintenance;
+use crate::sensors::a02yyuw::{A02yyuw, Message as LevelMessage};
+use crate::sensors::reading::Sensor;
+use crate::state_machines::{
+    operational_fsm::{OperationalState, Transitions},
+    ArcMutexState,
+};
+use crate::types::{Millimeters, Temperature};
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use serde::Serialize;
+use std::sync::{
+    mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+const LEVEL_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const HEATING_TOLERANCE: Temperature = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Path {
+    Group,
+    HotWater,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    FillTank,
+    Heat,
+    Flush { path: Path, duration: Duration },
+    Soak(Duration),
+    Refill,
+    Rinse { path: Path, duration: Duration },
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::FillTank => write!(f, "Fill the tank with descaler"),
+            Step::Heat => write!(f, "Heating"),
+            Step::Flush {
+                path: Path::Group, ..
+            } => write!(f, "Flushing group"),
+            Step::Flush {
+                path: Path::HotWater,
+                ..
+            } => write!(f, "Flushing hot water"),
+            Step::Soak(_) => write!(f, "Soaking"),
+            Step::Refill => write!(f, "Rinse and refill the tank with fresh water"),
+            Step::Rinse {
+                path: Path::Group, ..
+            } => write!(f, "Rinsing group"),
+            Step::Rinse {
+                path: Path::HotWater,
+                ..
+            } => write!(f, "Rinsing hot water"),
+        }
+    }
+}
+
+impl Step {
+    fn program(config: &Config) -> Vec<Step> {
+        let mut program = vec![Step::FillTank, Step::Heat];
+        for _ in 0..config.cycles {
+            program.push(Step::Flush {
+                path: Path::Group,
+                duration: config.group_flush_time,
+            });
+            program.push(Step::Flush {
+                path: Path::HotWater,
+                duration: config.hot_water_flush_time,
+            });
+            program.push(Step::Soak(config.soak_time));
+        }
+        program.push(Step::Refill);
+        for _ in 0..config.rinse_cycles {
+            program.push(Step::Rinse {
+                path: Path::Group,
+                duration: config.group_flush_time,
+            });
+            program.push(Step::Rinse {
+                path: Path::HotWater,
+                duration: config.hot_water_flush_time,
+            });
+        }
+        program
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        match self {
+            Step::Flush { duration, .. } | Step::Rinse { duration, .. } => Some(*duration),
+            Step::Soak(duration) => Some(*duration),
+            _ => None,
+        }
+    }
+
+    fn waits_for_user(&self) -> bool {
+        matches!(self, Step::FillTank | Step::Refill)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub step: String,
+    pub step_number: usize,
+    pub total_steps: usize,
+    pub percentage: f32,
+    pub waiting_for_user: bool,
+}
+
+pub enum Message {
+    Start,
+    Continue,
+    Abort,
+}
+
+#[derive(Clone)]
+pub struct Descale {
+    mailbox: Sender<Message>,
+}
+
+impl Descale {
+    pub fn send_message(&self, message: Message) {
+        self.mailbox.send(message).unwrap();
+    }
+
+    pub fn new(
+        pump: Pump,
+        boiler: Boiler,
+        level_sensor: A02yyuw,
+        level: Sensor<Millimeters>,
+        temperature_probe: Sensor<Temperature>,
+        operational_state: Arc<Mutex<OperationalState>>,
+        nvs: Option<EspDefaultNvsPartition>,
+        config: Config,
+    ) -> Self {
+        let (mailbox, rx) = channel::<Message>();
+
+        let descaler = DescaleInternal {
+            pump,
+            boiler,
+            level_sensor,
+            level,
+            temperature_probe,
+            operational_state,
+            nvs,
+            config,
+            program: Step::program(&config),
+            current: None,
+            step_started: Instant::now(),
+            confirmed: false,
+            next_level_poll: Instant::now(),
+            level_requested: None,
+        };
+
+        std::thread::Builder::new()
+            .name("Descale".to_string())
+            .spawn(move || descaler.run(rx))
+            .expect("Failed to spawn descale thread");
+
+        Self { mailbox }
+    }
+}
+
+struct DescaleInternal {
+    pump: Pump,
+    boiler: Boiler,
+    level_sensor: A02yyuw,
+    level: Sensor<Millimeters>,
+    temperature_probe: Sensor<Temperature>,
+    operational_state: Arc<Mutex<OperationalState>>,
+    nvs: Option<EspDefaultNvsPartition>,
+    config: Config,
+    program: Vec<Step>,
+    current: Option<usize>,
+    step_started: Instant,
+    confirmed: bool,
+    next_level_poll: Instant,
+    // When the last read was asked for, anything older was measured before it
+    level_requested: Option<Instant>,
+}
+
+impl DescaleInternal {
+    fn run(mut self, rx: Receiver<Message>) {
+        loop {
+            let message = if self.current.is_some() {
+                rx.recv_timeout(UPDATE_INTERVAL).ok()
+            } else {
+                rx.recv().ok()
+            };
+
+            match message {
+                Some(Message::Start) => self.start(),
+                Some(Message::Continue) => self.confirmed = true,
+                Some(Message::Abort) => self.stop(false),
+                None => {}
+            }
+
+            if let Some(index) = self.current {
+                if self.step_complete(self.program[index]) {
+                    self.enter_step(index + 1);
+                } else {
+                    self.report_progress();
+                }
+            }
+        }
+    }
+
+    fn start(&mut self) {
+        if self.current.is_some() {
+            log::warn!("Descale already in progress");
+            return;
+        }
+        let tank = self.level_sensor.config.read().unwrap().tank;
+        if let Err(e) = self.config.validate(&tank) {
+            log::warn!("Failed to start descale: {}", e);
+            return;
+        }
+        self.program = Step::program(&self.config);
+        if let Err(e) = self
+            .operational_state
+            .transition(Transitions::StartDescale(self.progress(0)))
+        {
+            log::warn!("Failed to start descale: {:?}", e);
+            return;
+        }
+
+        log::info!("Starting descale with {} steps", self.program.len());
+        self.boiler
+            .send_message(BoilerMessage::SetMode(BoilerMode::Mpc {
+                target: self.config.temperature,
+            }));
+        self.enter_step(0);
+    }
+
+    fn stop(&mut self, completed: bool) {
+        if self.current.is_none() {
+            return;
+        }
+        self.current = None;
+        self.pump.turn_off();
+        self.boiler
+            .send_message(BoilerMessage::SetMode(BoilerMode::Off));
+
+        if completed {
+            log::info!("Descale complete");
+            let mut maintenance = Maintenance::load_or_default(&self.nvs);
+            if let Err(e) = maintenance.record_descale() {
+                log::error!("Failed to save descale date: {:?}", e);
+            }
+        } else {
+            log::warn!("Descale aborted");
+        }
+
+        if let Err(e) = self.operational_state.transition(Transitions::Stop) {
+            log::error!("Failed to leave descale state: {:?}", e);
+        }
+    }
+
+    fn enter_step(&mut self, index: usize) {
+        let Some(step) = self.program.get(index).copied() else {
+            self.stop(true);
+            return;
+        };
+
+        log::info!(
+            "Descale step {}/{}: {}",
+            index + 1,
+            self.program.len(),
+            step
+        );
+        self.current = Some(index);
+        self.step_started = Instant::now();
+        self.confirmed = false;
+        self.next_level_poll = Instant::now();
+        self.level_requested = None;
+
+        match step {
+            Step::Flush {
+                path: Path::Group,
+                duration,
+            }
+            | Step::Rinse {
+                path: Path::Group,
+                duration,
+            } => self.pump.turn_on(Some(duration)),
+            Step::Flush {
+                path: Path::HotWater,
+                ..
+            }
+            | Step::Rinse {
+                path: Path::HotWater,
+                ..
+            } => self.pump.turn_on_for_hot_water(),
+            Step::FillTank | Step::Heat | Step::Soak(_) | Step::Refill => self.pump.turn_off(),
+        }
+        self.report_progress();
+    }
+
+    fn step_complete(&mut self, step: Step) -> bool {
+        match step {
+            Step::FillTank => self.confirmed,
+            Step::Heat => {
+                let temperature = self.temperature_probe.get();
+                temperature >= self.config.temperature - HEATING_TOLERANCE
+            }
+            Step::Refill => {
+                let reading = self.level.reading();
+                let fresh = matches!(
+                    (self.level_requested, reading.timestamp),
+                    (Some(requested), Some(taken)) if taken > requested
+                );
+                let tank = self.level_sensor.config.read().unwrap().tank;
+                if fresh
+                    && self.level.is_valid()
+                    && tank.volume(reading.value) >= self.config.refill_volume
+                {
+                    return true;
+                }
+                let now = Instant::now();
+                if now >= self.next_level_poll {
+                    self.next_level_poll = now + LEVEL_POLL_INTERVAL;
+                    self.level_requested = Some(now);
+                    self.level_sensor.send_message(LevelMessage::DoRead);
+                }
+                false
+            }
+            Step::Flush { duration, .. } | Step::Rinse { duration, .. } => {
+                if self.step_started.elapsed() >= duration {
+                    self.pump.turn_off();
+                    true
+                } else {
+                    false
+                }
+            }
+            Step::Soak(duration) => self.step_started.elapsed() >= duration,
+        }
+    }
+
+    fn progress(&self, index: usize) -> Progress {
+        let total_steps = self.program.len();
+        let step = self.program.get(index);
+        let step_fraction = step
+            .and_then(|step| step.duration())
+            .map(|duration| {
+                (self.step_started.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0)
+            })
+            .unwrap_or(0.0);
+
+        Progress {
+            step: step.map(|step| step.to_string()).unwrap_or_default(),
+            step_number: index + 1,
+            total_steps,
+            percentage: (index as f32 + step_fraction) / total_steps as f32 * 100.0,
+            waiting_for_user: step.map(|step| step.waits_for_user()).unwrap_or(false),
+        }
+    }
+
+    fn report_progress(&self) {
+        if let Some(index) = self.current {
+            let progress = self.progress(index);
+            if let Err(e) = self
+                .operational_state
+                .transition(Transitions::DescaleProgress(progress))
+            {
+                log::warn!("Failed to report descale progress: {:?}", e);
+            }
+        }
+    }
+}
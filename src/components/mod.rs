@@ -1,4 +1,8 @@
 pub mod boiler;
+pub mod descale;
 pub mod pump;
 #[cfg(feature = "sdcard")]
 pub mod sd_card;
+#[cfg(feature = "sdcard")]
+pub mod shot_log;
+pub mod valve;
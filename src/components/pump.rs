@@ -1,7 +1,12 @@
+use crate::components::valve::{Route, Valve};
 use crate::config::Pump as Config;
 use crate::gpio::pwm::Pwm;
+use crate::schemas::command::Command;
+use crate::sensors::a02yyuw::{A02yyuw, Draw, Message as LevelMessage};
+use crate::sensors::reading::Sensor;
+use crate::sensors::registry::Registry;
 use crate::types::*;
-use esp_idf_svc::hal::gpio::{Output, OutputPin, PinDriver};
+use esp_idf_svc::hal::gpio::OutputPin;
 use std::sync::{
     mpsc::{channel, Sender},
     Arc, RwLock,
@@ -24,17 +29,29 @@ pub type Mailbox = Sender<Message>;
 #[derive(Clone)]
 pub struct Pump {
     mailbox: Mailbox,
+    duty_cycle: Arc<RwLock<f32>>,
 }
 
 impl Pump {
+    // `commands` is told when the pump ends a shot by itself, so the operational state follows
     pub fn new<PD: OutputPin, PE: OutputPin>(
         pump_pin: PD,
-        solenoid_pin: PE,
-        pressure_probe: Arc<RwLock<Bar>>,
-        weight_probe: Arc<RwLock<Grams>>,
+        valve: Valve<PE>,
+        sensors: &Registry,
+        cup_removed: Arc<RwLock<bool>>,
+        level_sensor: A02yyuw,
+        commands: Sender<Command>,
         config: Config,
     ) -> Self {
-        PumpInternal::start(pump_pin, solenoid_pin, pressure_probe, weight_probe, config)
+        PumpInternal::start(
+            pump_pin,
+            valve,
+            sensors,
+            cup_removed,
+            level_sensor,
+            commands,
+            config,
+        )
     }
     pub fn turn_on(&self, duration: Option<Duration>) {
         if let Some(duration) = duration {
@@ -64,20 +81,35 @@ impl Pump {
     pub fn backflush(&self) {
         self.mailbox.send(Message::Backflush).unwrap();
     }
+    pub fn get_duty_cycle(&self) -> f32 {
+        *self.duty_cycle.read().unwrap()
+    }
 }
 
 enum State {
     On(Option<Instant>),
     Off,
-    OnForYield { start: Grams, target: Grams },
+    OnForYield {
+        start: Grams,
+        target: Grams,
+        started: Instant,
+    },
     Backflush,
 }
 
 struct PumpInternal<PD: OutputPin, PE: OutputPin> {
     pwm: Pwm<'static, PD>,
-    solenoid: PinDriver<'static, PE, Output>,
-    pressure_probe: Arc<RwLock<Bar>>,
-    weight_probe: Arc<RwLock<Grams>>,
+    duty_cycle: Arc<RwLock<f32>>,
+    valve: Valve<PE>,
+    pressure: Bar,
+    route: Route,
+    pressure_probe: Sensor<Bar>,
+    weight_probe: Sensor<Grams>,
+    cup_removed: Arc<RwLock<bool>>,
+    cup_removed_since: Option<Instant>,
+    level_sensor: A02yyuw,
+    commands: Sender<Command>,
+    draw: Option<Draw>,
     state: State,
     backflush_cycle_start: Instant,
     backflush_in_off_cycle: bool,
@@ -91,19 +123,33 @@ where
 {
     fn start(
         pump_pin: PD,
-        solenoid_pin: PE,
-        pressure_probe: Arc<RwLock<Bar>>,
-        weight_probe: Arc<RwLock<Grams>>,
+        valve: Valve<PE>,
+        sensors: &Registry,
+        cup_removed: Arc<RwLock<bool>>,
+        level_sensor: A02yyuw,
+        commands: Sender<Command>,
         config: Config,
     ) -> Pump {
+        let pressure_probe = sensors.pressure.clone();
+        let weight_probe = sensors.weight.clone();
         let (tx, rx) = channel();
+        let duty_cycle = Arc::new(RwLock::new(0.0));
+        let my_duty_cycle = duty_cycle.clone();
 
         std::thread::spawn(move || {
             let mut my_pump = PumpInternal {
                 pwm: Pwm::new(pump_pin, config.pwm_period, None),
-                solenoid: PinDriver::output(solenoid_pin).expect("Failed to create relay"),
+                duty_cycle: my_duty_cycle,
+                valve,
+                pressure: 0.0,
+                route: Route::Group,
                 pressure_probe,
                 weight_probe,
+                cup_removed,
+                cup_removed_since: None,
+                level_sensor,
+                commands,
+                draw: None,
                 state: State::Off,
                 backflush_cycle_start: Instant::now(),
                 backflush_in_off_cycle: true,
@@ -116,12 +162,30 @@ where
 
                 match my_pump.state {
                     State::On(Some(end)) if Instant::now() > end => {
-                        my_pump.trasition(Message::Off);
+                        my_pump.finish();
+                    }
+                    State::OnForYield { started, .. }
+                        if started.elapsed() > config.max_shot_time =>
+                    {
+                        log::warn!(
+                            "Shot ran for {:?} without reaching its yield",
+                            started.elapsed()
+                        );
+                        my_pump.abandon();
+                    }
+                    // The weight means nothing with the cup off the scale, give it a while to come back
+                    State::OnForYield { .. } if *my_pump.cup_removed.read().unwrap() => {
+                        let since = *my_pump.cup_removed_since.get_or_insert_with(Instant::now);
+                        if since.elapsed() > config.cup_grace {
+                            log::warn!("Cup off the scale for {:?} during a shot", since.elapsed());
+                            my_pump.abandon();
+                        }
                     }
-                    State::OnForYield { start, target } => {
-                        let current_scale = *my_pump.weight_probe.read().unwrap();
+                    State::OnForYield { start, target, .. } => {
+                        my_pump.cup_removed_since = None;
+                        let current_scale = my_pump.weight_probe.get();
                         if current_scale - start >= target {
-                            my_pump.trasition(Message::Off);
+                            my_pump.finish();
                         }
                     }
                     State::Backflush => {
@@ -143,32 +207,90 @@ where
                     _ => {}
                 }
 
-                let next_tick = [Some(config.pwm_period), my_pump.pwm.tick()]
-                    .iter()
-                    .filter_map(|x| *x)
-                    .min()
-                    .unwrap(); // this is safe, we've already inserted a default value
+                let pressure = my_pump.pressure;
+                let route = my_pump.route;
+                my_pump.valve.interlock(pressure, route);
+
+                let next_tick = [
+                    Some(config.pwm_period),
+                    my_pump.pwm.tick(),
+                    my_pump.valve.tick(),
+                ]
+                .iter()
+                .filter_map(|x| *x)
+                .min()
+                .unwrap(); // this is safe, we've already inserted a default value
 
                 std::thread::sleep(next_tick);
             }
         });
-        Pump { mailbox: tx }
+        Pump {
+            mailbox: tx,
+            duty_cycle,
+        }
     }
 
     fn set_pressure(&mut self, pressure: Bar) {
-        self.pwm
-            .set_duty_cycle(self.pressure_to_duty_cycle(pressure));
+        self.pressure = pressure;
+        let duty_cycle = self.pressure_to_duty_cycle(pressure);
+        self.pwm.set_duty_cycle(duty_cycle);
+        *self.duty_cycle.write().unwrap() = duty_cycle;
+    }
+
+    // Stopping on its own, rather than being told to
+    fn finish(&mut self) {
+        self.trasition(Message::Off);
+        if let Err(e) = self.commands.send(Command::ShotFinished) {
+            log::error!("Failed to report the end of the shot: {:?}", e);
+        }
+    }
+
+    // Giving up on the yield, the system raises a warning for it
+    fn abandon(&mut self) {
+        self.trasition(Message::Off);
+        if let Err(e) = self.commands.send(Command::ShotAbandoned) {
+            log::error!("Failed to report the abandoned shot: {:?}", e);
+        }
     }
 
     fn open_valve(&mut self) {
-        self.solenoid.set_high().unwrap();
+        self.valve.open();
     }
 
     fn close_valve(&mut self) {
-        self.solenoid.set_low().unwrap();
+        self.valve.close();
+    }
+
+    // Tell the level sensor what the water is being used for so it can account for it
+    fn set_draw(&mut self, draw: Option<Draw>) {
+        if self.draw == draw {
+            return;
+        }
+        self.draw = draw;
+        self.level_sensor.send_message(match draw {
+            Some(draw) => LevelMessage::StartDraw(draw),
+            None => LevelMessage::EndDraw,
+        });
     }
 
     fn trasition(&mut self, message: Message) {
+        let draw = match message {
+            Message::Off => None,
+            Message::OnForHotWater => Some(Draw::HotWater),
+            Message::Backflush => Some(Draw::Backflush),
+            Message::SetPressure(_) => self.draw.or(Some(Draw::Shot)),
+            Message::On
+            | Message::OnForTime(_)
+            | Message::OnForTimeAtPressure(..)
+            | Message::OnForYield { .. } => Some(Draw::Shot),
+        };
+        self.set_draw(draw);
+        self.route = match message {
+            Message::OnForHotWater => Route::SteamWand,
+            Message::SetPressure(_) | Message::Off => self.route,
+            _ => Route::Group,
+        };
+
         match message {
             Message::On => {
                 self.state = State::On(None);
@@ -177,13 +299,16 @@ where
             }
             Message::Off => {
                 self.state = State::Off;
-                self.close_valve();
-                self.pwm.set_duty_cycle(0.0);
+                self.set_pressure(0.0);
+                match self.route {
+                    Route::Group => self.valve.release(),
+                    Route::SteamWand => self.close_valve(),
+                }
+                self.valve.save_actuations();
             }
             Message::SetPressure(pressure) => {
                 self.state = State::On(None);
-                self.pwm
-                    .set_duty_cycle(self.pressure_to_duty_cycle(pressure));
+                self.set_pressure(pressure);
             }
             Message::OnForTime(duration) => {
                 self.state = State::On(Some(Instant::now() + duration));
@@ -196,14 +321,15 @@ where
                 self.set_pressure(pressure);
             }
             Message::OnForYield { pressure, grams } => {
-                let current_scale = *self.weight_probe.read().unwrap();
+                let current_scale = self.weight_probe.get();
                 self.open_valve();
+                self.cup_removed_since = None;
                 self.state = State::OnForYield {
                     start: current_scale,
                     target: grams,
+                    started: Instant::now(),
                 };
-                self.pwm
-                    .set_duty_cycle(self.pressure_to_duty_cycle(pressure));
+                self.set_pressure(pressure);
             }
             Message::OnForHotWater => {
                 self.state = State::On(None);
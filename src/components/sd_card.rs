@@ -19,6 +19,7 @@ pub struct SdCard {
 impl SdCard {
     pub const SD_MOUNT_POINT: &'static str = "/sdcard";
     pub const DRINKS_DIRECTORY: &'static str = "/sdcard/drinks";
+    pub const SHOTS_DIRECTORY: &'static str = "/sdcard/shots";
     pub fn new<SPI: SpiAnyPins>(
         spi: impl Peripheral<P = SPI> + 'static,
         sclk: impl Peripheral<P = impl OutputPin> + 'static,
@@ -63,11 +64,13 @@ impl SdCard {
                 log::error!("Failed to mount filesystem: {}", e);
             })?;
 
-        if !fs::exists(Self::DRINKS_DIRECTORY)? {
-            log::info!("Creating {}", Self::DRINKS_DIRECTORY);
-            fs::create_dir(Self::DRINKS_DIRECTORY).inspect_err(|e| {
-                log::error!("Failed to create directory: {}", e);
-            })?;
+        for directory in [Self::DRINKS_DIRECTORY, Self::SHOTS_DIRECTORY] {
+            if !fs::exists(directory)? {
+                log::info!("Creating {}", directory);
+                fs::create_dir(directory).inspect_err(|e| {
+                    log::error!("Failed to create directory: {}", e);
+                })?;
+            }
         }
 
         Ok(SdCard {
@@ -0,0 +1,448 @@
+use crate::app_state::System;
+use crate::components::boiler::Boiler;
+use crate::components::pump::Pump;
+use crate::components::sd_card::SdCard;
+use crate::config::Telemetry as Config;
+use crate::models::extraction::{self, Metrics, Reading};
+use crate::schemas::drink::Menu;
+use crate::schemas::event::EventBuffer;
+use crate::sensors::registry::Registry;
+use crate::sensors::scale::Interface as LoadCell;
+use crate::state_machines::operational_fsm::OperationalState;
+use crate::types::{Bar, Grams, Temperature, Watts};
+use serde::{Deserialize, Serialize};
+use std::fs::{read_dir, remove_file, File};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const ROWS_PER_CHUNK: usize = 32;
+// Anything shorter would spin the thread, and zero would divide by zero sizing the log
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Kind {
+    Shot,
+    Steam,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Sample {
+    pub temperature: Temperature,
+    pub pressure: Bar,
+    pub weight: Grams,
+    pub flow: f32,
+    pub pump_duty: f32,
+    pub heater_power: Watts,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+pub struct Summary {
+    pub duration: Duration,
+    pub mean_temperature: Temperature,
+    pub final_weight: Grams,
+    pub peak_flow: f32,
+    pub energy_wh: f32,
+    pub metrics: Metrics,
+}
+
+// Filled in by hand after the shot, the dose isn't something the machine can weigh
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Annotation {
+    pub dose: Option<Grams>,
+    pub rating: Option<u8>,
+    pub notes: Option<String>,
+}
+
+impl Annotation {
+    pub const MAX_RATING: u8 = 5;
+    const MAX_NOTES_LEN: usize = 512;
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if matches!(self.rating, Some(rating) if rating == 0 || rating > Self::MAX_RATING) {
+            return Err(anyhow::anyhow!(
+                "Rating must be between 1 and {}",
+                Self::MAX_RATING
+            ));
+        }
+        if matches!(&self.notes, Some(notes) if notes.len() > Self::MAX_NOTES_LEN) {
+            return Err(anyhow::anyhow!(
+                "Notes can be at most {} bytes",
+                Self::MAX_NOTES_LEN
+            ));
+        }
+        if matches!(self.dose, Some(dose) if dose <= 0.0) {
+            return Err(anyhow::anyhow!("Dose must be positive"));
+        }
+        Ok(())
+    }
+}
+
+// Serialised ahead of the samples, so it can be read without decoding the whole series
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Header {
+    pub kind: Kind,
+    pub drink: Option<String>,
+    // Seconds since the epoch, zero if SNTP hadn't set the clock
+    pub started: u64,
+    pub sample_interval: Duration,
+    pub target_temperature: Option<Temperature>,
+    pub summary: Summary,
+    pub annotation: Annotation,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShotLog {
+    pub header: Header,
+    pub samples: Vec<Sample>,
+}
+
+impl ShotLog {
+    // 8.3 filesystem
+    const SHOT_FILE_EXTENSION: &'static str = "SHT";
+
+    fn new(
+        kind: Kind,
+        drink: Option<String>,
+        sample_interval: Duration,
+        target_temperature: Option<Temperature>,
+        dose: Option<Grams>,
+    ) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Self {
+            header: Header {
+                kind,
+                drink,
+                started,
+                sample_interval,
+                target_temperature,
+                summary: Summary::default(),
+                annotation: Annotation {
+                    dose,
+                    ..Default::default()
+                },
+            },
+            samples: vec![],
+        }
+    }
+
+    fn summarise(&mut self) {
+        let count = self.samples.len().max(1) as f32;
+        let interval = self.header.sample_interval;
+        self.header.summary = Summary {
+            duration: interval * self.samples.len() as u32,
+            mean_temperature: self.samples.iter().map(|s| s.temperature).sum::<f32>() / count,
+            final_weight: self.samples.last().map(|s| s.weight).unwrap_or_default(),
+            peak_flow: self.samples.iter().map(|s| s.flow).fold(0.0, f32::max),
+            energy_wh: self.samples.iter().map(|s| s.heater_power).sum::<f32>()
+                * interval.as_secs_f32()
+                / 3600.0,
+            metrics: Metrics::compute(
+                &self.samples.iter().map(Reading::from).collect::<Vec<_>>(),
+                interval,
+                self.header.target_temperature,
+                self.header.annotation.dose,
+            ),
+        };
+    }
+
+    fn path(number: u32) -> String {
+        format!(
+            "{}/{}.{}",
+            SdCard::SHOTS_DIRECTORY,
+            number,
+            Self::SHOT_FILE_EXTENSION
+        )
+    }
+
+    // Every log on the card by number, skipping anything that isn't one of ours
+    pub fn numbers() -> anyhow::Result<Vec<u32>> {
+        let directory = read_dir(SdCard::SHOTS_DIRECTORY).inspect_err(|e| {
+            log::error!("Failed to read directory: {}", e);
+        })?;
+
+        let mut numbers: Vec<u32> = directory
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| {
+                let (number, extension) = name.split_once('.')?;
+                if extension != Self::SHOT_FILE_EXTENSION {
+                    return None;
+                }
+                number.parse().ok()
+            })
+            .collect();
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    fn read(number: u32) -> anyhow::Result<Vec<u8>> {
+        let path = Self::path(number);
+        let mut data = vec![];
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .inspect_err(|e| {
+                log::error!("Failed to read file {}: {}", path, e);
+            })?;
+        Ok(data)
+    }
+
+    pub fn load(number: u32) -> anyhow::Result<Self> {
+        Ok(postcard::from_bytes(&Self::read(number)?)?)
+    }
+
+    pub fn load_header(number: u32) -> anyhow::Result<Header> {
+        let (header, _) = postcard::take_from_bytes(&Self::read(number)?)?;
+        Ok(header)
+    }
+
+    pub fn delete(number: u32) -> anyhow::Result<()> {
+        let path = Self::path(number);
+        remove_file(&path).inspect_err(|e| {
+            log::error!("Failed to remove file {}: {}", path, e);
+        })?;
+        Ok(())
+    }
+
+    pub fn annotate(number: u32, annotation: Annotation) -> anyhow::Result<Header> {
+        annotation.validate()?;
+        let mut log = Self::load(number)?;
+        log.header.summary.metrics.brew_ratio =
+            extraction::brew_ratio(log.header.summary.final_weight, annotation.dose);
+        log.header.annotation = annotation;
+        log.write(number)?;
+        Ok(log.header)
+    }
+
+    // Batches of rows rather than one string, so the whole series never has to be in
+    // memory as text
+    pub fn csv_chunks(&self) -> impl Iterator<Item = String> + '_ {
+        let header = format!(
+            "# {:?} {} started {}\ntime_s,temperature,pressure,weight,flow,pump_duty,heater_power\n",
+            self.header.kind,
+            self.header.drink.as_deref().unwrap_or("-"),
+            self.header.started
+        );
+        let interval = self.header.sample_interval.as_secs_f32();
+        let rows = self
+            .samples
+            .chunks(ROWS_PER_CHUNK)
+            .enumerate()
+            .map(move |(chunk, samples)| {
+                samples
+                    .iter()
+                    .enumerate()
+                    .map(|(index, s)| {
+                        let time = (chunk * ROWS_PER_CHUNK + index) as f32 * interval;
+                        format!(
+                            "{:.2},{:.2},{:.2},{:.2},{:.2},{:.3},{:.0}\n",
+                            time,
+                            s.temperature,
+                            s.pressure,
+                            s.weight,
+                            s.flow,
+                            s.pump_duty,
+                            s.heater_power
+                        )
+                    })
+                    .collect::<String>()
+            });
+        std::iter::once(header).chain(rows)
+    }
+
+    pub fn json_chunks(&self) -> impl Iterator<Item = anyhow::Result<String>> + '_ {
+        let header = serde_json::to_string(&self.header)
+            .map(|header| format!("{{\"header\":{},\"samples\":[", header));
+        let samples = self.samples.chunks(ROWS_PER_CHUNK).enumerate().map(
+            |(chunk, samples)| -> anyhow::Result<String> {
+                let rows = serde_json::to_string(samples)?;
+                // Drop the brackets so the batches join up into one array
+                let rows = &rows[1..rows.len() - 1];
+                Ok(if chunk == 0 {
+                    rows.to_string()
+                } else {
+                    format!(",{}", rows)
+                })
+            },
+        );
+        std::iter::once(header.map_err(anyhow::Error::from))
+            .chain(samples)
+            .chain(std::iter::once(Ok("]}".to_string())))
+    }
+
+    fn write(&self, number: u32) -> anyhow::Result<()> {
+        let path = Self::path(number);
+        let data = postcard::to_allocvec(self)?;
+        let mut file = File::create(&path).inspect_err(|e| {
+            log::error!("Failed to create file {}: {}", path, e);
+        })?;
+        file.write_all(&data).inspect_err(|e| {
+            log::error!("Failed to write to file {}: {}", path, e);
+        })?;
+        Ok(())
+    }
+
+    fn save(&self) -> anyhow::Result<u32> {
+        let number = Self::numbers()?.last().map_or(0, |last| last + 1);
+        self.write(number)?;
+        Ok(number)
+    }
+}
+
+impl From<&Sample> for Reading {
+    fn from(sample: &Sample) -> Self {
+        Reading {
+            temperature: sample.temperature,
+            pressure: sample.pressure,
+            weight: sample.weight,
+            flow: sample.flow,
+        }
+    }
+}
+
+pub struct Recorder {
+    operational_state: Arc<Mutex<OperationalState>>,
+    sensors: Registry,
+    scale: LoadCell,
+    pump: Pump,
+    boiler: Boiler,
+    selected_drink: Arc<RwLock<Option<u32>>>,
+    menu: Arc<RwLock<Menu>>,
+    events: Arc<Mutex<EventBuffer>>,
+    last_shot: Arc<RwLock<Option<Metrics>>>,
+    // Without a card the metrics are still worked out, there's just nowhere to keep the log
+    save: bool,
+    config: Config,
+}
+
+impl Recorder {
+    pub fn start(system: &System) {
+        let mut recorder = Self {
+            operational_state: system.operational_state.clone(),
+            sensors: system.board.sensors.clone(),
+            scale: system.board.scale.clone(),
+            pump: system.board.pump.clone(),
+            boiler: system.board.boiler.clone(),
+            selected_drink: system.selected_drink.clone(),
+            menu: system.menu.clone(),
+            events: system.events.clone(),
+            last_shot: system.last_shot.clone(),
+            save: *system.sd_card_present,
+            config: system.config.read().unwrap().telemetry,
+        };
+        if recorder.config.sample_interval < MIN_SAMPLE_INTERVAL {
+            log::warn!(
+                "Telemetry sample interval {:?} is too short, using {:?}",
+                recorder.config.sample_interval,
+                MIN_SAMPLE_INTERVAL
+            );
+            recorder.config.sample_interval = MIN_SAMPLE_INTERVAL;
+        }
+        std::thread::Builder::new()
+            .name("Shot log".to_string())
+            .spawn(move || recorder.run())
+            .expect("Failed to spawn shot log thread");
+    }
+
+    fn kind(&self) -> Option<Kind> {
+        match *self.operational_state.lock().unwrap() {
+            OperationalState::Brewing(_) => Some(Kind::Shot),
+            OperationalState::Steaming if self.config.log_steam => Some(Kind::Steam),
+            _ => None,
+        }
+    }
+
+    fn drink(&self) -> Option<String> {
+        let number = (*self.selected_drink.read().unwrap())?;
+        self.menu.read().unwrap().get(&number).cloned()
+    }
+
+    fn sample(&self) -> Sample {
+        Sample {
+            temperature: self.sensors.temperature.get(),
+            pressure: self.sensors.pressure.get(),
+            weight: self.sensors.weight.get(),
+            flow: self.scale.get_flow(),
+            pump_duty: self.pump.get_duty_cycle(),
+            heater_power: self.boiler.get_power(),
+        }
+    }
+
+    fn finish(&self, mut log: ShotLog) {
+        if log.samples.is_empty() {
+            return;
+        }
+        log.summarise();
+        let summary = log.header.summary;
+        if log.header.kind == Kind::Shot {
+            *self.last_shot.write().unwrap() = Some(summary.metrics);
+        }
+
+        let description = format!(
+            "{:.1}g in {:.0}s{}",
+            summary.final_weight,
+            summary.metrics.contact_time,
+            summary
+                .metrics
+                .brew_ratio
+                .map(|ratio| format!(" (1:{:.1})", ratio))
+                .unwrap_or_default()
+        );
+        let message = if !self.save {
+            format!(
+                "{:?} not saved, no SD card: {}",
+                log.header.kind, description
+            )
+        } else {
+            match log.save() {
+                Ok(number) => format!("{:?} saved as {}: {}", log.header.kind, number, description),
+                Err(e) => format!("Failed to save {:?} log: {:?}", log.header.kind, e),
+            }
+        };
+        log::info!("{}", message);
+        self.events.lock().unwrap().info(module_path!(), message);
+    }
+
+    fn run(self) {
+        let max_samples = (self.config.max_duration.as_millis()
+            / self.config.sample_interval.as_millis()) as usize;
+        let mut session: Option<ShotLog> = None;
+        let mut next_sample = Instant::now();
+        loop {
+            let kind = self.kind();
+            match (session.as_mut(), kind) {
+                (Some(log), Some(kind)) if log.header.kind == kind => {
+                    if log.samples.len() < max_samples {
+                        log.samples.push(self.sample());
+                    }
+                }
+                (None, None) => {}
+                (_, kind) => {
+                    if let Some(log) = session.take() {
+                        self.finish(log);
+                    }
+                    session = kind.map(|kind| {
+                        ShotLog::new(
+                            kind,
+                            self.drink(),
+                            self.config.sample_interval,
+                            self.boiler.get_target(),
+                            self.config.dose,
+                        )
+                    });
+                }
+            }
+
+            // Sleep to the next slot rather than for a fixed time so the rate doesn't drift
+            next_sample += self.config.sample_interval;
+            let now = Instant::now();
+            if next_sample > now {
+                std::thread::sleep(next_sample - now);
+            } else {
+                next_sample = now;
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::config::Valve as Config;
+use crate::gpio::relay::{Relay, State as RelayState};
+use crate::maintenance::Maintenance;
+use crate::types::Bar;
+use esp_idf_svc::hal::gpio::OutputPin;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Route {
+    Group,
+    SteamWand,
+}
+
+// The three-way solenoid between the pump and the group. Energised it lets water
+// through to the group, released it vents the group to the drip tray.
+pub struct Valve<PD: OutputPin> {
+    relay: Relay<'static, PD>,
+    energised: bool,
+    actuations: u64,
+    unsaved: bool,
+    nvs: Option<EspDefaultNvsPartition>,
+    config: Config,
+}
+
+impl<PD> Valve<PD>
+where
+    PD: OutputPin,
+{
+    pub fn new(
+        pin: PD,
+        inverted: bool,
+        nvs: Option<EspDefaultNvsPartition>,
+        config: Config,
+    ) -> Self {
+        let actuations = Maintenance::load_or_default(&nvs).valve_actuations;
+        Self {
+            relay: Relay::new(pin, Some(inverted)),
+            energised: false,
+            actuations,
+            unsaved: false,
+            nvs,
+            config,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        matches!(self.relay.state, RelayState::On | RelayState::OnUntil(_))
+    }
+
+    pub fn open(&mut self) {
+        self.relay.turn_on(None);
+        self.count_actuation();
+    }
+
+    pub fn close(&mut self) {
+        self.relay.turn_off(None);
+        self.count_actuation();
+    }
+
+    // Hold the valve open for a moment after the shot so the puck isn't pulled
+    // apart by the sudden drop in pressure, then vent
+    pub fn release(&mut self) {
+        if self.relay.state == RelayState::On {
+            self.relay.turn_on(Some(self.config.release_delay));
+        }
+    }
+
+    // Force the valve into the only safe position for where the pump is pushing water
+    pub fn interlock(&mut self, pressure: Bar, route: Route) {
+        if pressure < self.config.interlock_pressure {
+            return;
+        }
+
+        match route {
+            Route::Group if !self.is_open() => {
+                log::warn!(
+                    "Interlock: opening valve, pump at {:.1} bar to the group",
+                    pressure
+                );
+                self.open();
+            }
+            Route::SteamWand if self.is_open() => {
+                log::warn!(
+                    "Interlock: closing valve, pump at {:.1} bar to the steam wand",
+                    pressure
+                );
+                self.close();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<Duration> {
+        let next = self.relay.tick();
+        self.count_actuation();
+        next
+    }
+
+    fn count_actuation(&mut self) {
+        let energised = self.is_open();
+        if energised != self.energised {
+            self.energised = energised;
+            if energised {
+                self.actuations += 1;
+                self.unsaved = true;
+            }
+        }
+    }
+
+    // Called when the pump stops, saving every actuation would wear out the flash
+    pub fn save_actuations(&mut self) {
+        if !self.unsaved {
+            return;
+        }
+
+        let mut maintenance = Maintenance::load_or_default(&self.nvs);
+        match maintenance.record_valve_actuations(self.actuations) {
+            Ok(()) => self.unsaved = false,
+            Err(e) => log::error!("Failed to save valve actuations: {:?}", e),
+        }
+    }
+}
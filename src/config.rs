@@ -7,13 +7,21 @@ use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Config {
+    pub board: Option<crate::board_profile::Profile>,
     pub mqtt: Mqtt,
     pub load_cell: LoadCell,
     pub adc: Adc,
     pub boiler: Boiler,
     pub pump: Pump,
+    pub pressure_sensor: crate::sensors::pressure::Transducer,
     pub level_sensor: LevelSensor,
+    pub temperature_probes: TemperatureProbes,
+    pub switches: Switches,
+    pub buttons: Buttons,
     pub indicator: Indicator,
+    pub display: Display,
+    pub telemetry: Telemetry,
+    pub descale: Descale,
 
     #[serde(skip)]
     pub nvs: Option<EspDefaultNvsPartition>,
@@ -127,10 +135,17 @@ impl Default for LoadCell {
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub enum AdcBackend {
+    OnChip,
+    Ads1115(crate::gpio::ads1115::Config),
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone)]
 pub struct Adc {
     pub polling_interval: Duration,
     pub window: usize,
+    pub backend: AdcBackend,
 }
 
 impl Default for Adc {
@@ -141,15 +156,25 @@ impl Default for Adc {
         Adc {
             polling_interval: ADC_POLLING_RATE_MS,
             window: ADC_SAMPLES,
+            backend: AdcBackend::OnChip,
         }
     }
 }
 
+// Calibration points hold raw probe readings, so clear them after switching probe
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub enum BoilerProbe {
+    AnalogPt100,
+    Max31865(crate::sensors::max31865::Config),
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone)]
 pub struct Boiler {
     pub pwm_period: Duration,
     pub power: Watts,
+    pub probe: BoilerProbe,
     pub pt100_calibration_factor: f32,
+    pub pt100_calibration: crate::sensors::pt100::Calibration,
     pub mpc: Mpc,
 }
 
@@ -162,7 +187,9 @@ impl Default for Boiler {
         Boiler {
             pwm_period: BOILER_PWM_PERIOD,
             power: BOILER_POWER,
+            probe: BoilerProbe::AnalogPt100,
             pt100_calibration_factor: PT_100_CALIBRATION_FACTOR,
+            pt100_calibration: crate::sensors::pt100::Calibration::default(),
             mpc: Mpc::default(),
         }
     }
@@ -213,6 +240,10 @@ pub struct Pump {
     pub max_pressure: Bar,
     pub backflush_on_time: Duration,
     pub backflush_off_time: Duration,
+    pub valve: Valve,
+    // A shot stopped by yield gives up when the cup is off the scale this long, or it runs this long
+    pub cup_grace: Duration,
+    pub max_shot_time: Duration,
 }
 impl Default for Pump {
     fn default() -> Self {
@@ -220,32 +251,144 @@ impl Default for Pump {
         const MAX_PUMP_PRESSURE: Bar = 15.0;
         const BACKFLUSH_ON_TIME: Duration = Duration::from_secs(10);
         const BACKFLUSH_OFF_TIME: Duration = Duration::from_secs(10);
+        const CUP_GRACE: Duration = Duration::from_secs(10);
+        const MAX_SHOT_TIME: Duration = Duration::from_secs(90);
         Pump {
             pwm_period: PUMP_PWM_PERIOD,
             max_pressure: MAX_PUMP_PRESSURE,
             backflush_on_time: BACKFLUSH_ON_TIME,
             backflush_off_time: BACKFLUSH_OFF_TIME,
+            valve: Valve::default(),
+            cup_grace: CUP_GRACE,
+            max_shot_time: MAX_SHOT_TIME,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Valve {
+    pub release_delay: Duration,
+    pub interlock_pressure: Bar,
+}
+impl Default for Valve {
+    fn default() -> Self {
+        const VALVE_RELEASE_DELAY: Duration = Duration::from_secs(2);
+        const VALVE_INTERLOCK_PRESSURE: Bar = 2.0;
+        Valve {
+            release_delay: VALVE_RELEASE_DELAY,
+            interlock_pressure: VALVE_INTERLOCK_PRESSURE,
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone)]
 pub struct LevelSensor {
-    pub low_level_threshold: Millimeters,
+    pub tank: crate::sensors::tank::Geometry,
+    pub low_level_threshold: Milliliters,
 }
 impl Default for LevelSensor {
     fn default() -> Self {
-        const LOW_LEVEL_THRESHOLD: Millimeters = 100;
+        const LOW_LEVEL_THRESHOLD: Milliliters = 300.0;
         LevelSensor {
+            tank: crate::sensors::tank::Geometry::default(),
             low_level_threshold: LOW_LEVEL_THRESHOLD,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone, Default)]
+pub struct TemperatureProbes {
+    pub assignments:
+        [Option<crate::sensors::ambient::Assignment>; crate::sensors::ambient::MAX_PROBES],
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Switches {
+    pub poll_interval: Duration,
+    pub debounce: Duration,
+    pub mapping: [Option<crate::gpio::switch::Mapping>; crate::gpio::switch::MAX_MAPPINGS],
+    pub favourite: Favourite,
+}
+impl Default for Switches {
+    fn default() -> Self {
+        const SWITCH_POLL_INTERVAL: Duration = Duration::from_millis(10);
+        const SWITCH_DEBOUNCE: Duration = Duration::from_millis(50);
+        Switches {
+            poll_interval: SWITCH_POLL_INTERVAL,
+            debounce: SWITCH_DEBOUNCE,
+            mapping: crate::gpio::switch::Mapping::DEFAULT,
+            favourite: Favourite::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Buttons {
+    pub poll_interval: Duration,
+    pub debounce: Duration,
+    pub long_press: Duration,
+    pub double_press: Duration,
+    pub actions: [Option<crate::gpio::button::Actions>; crate::gpio::button::MAX_BUTTONS],
+}
+impl Default for Buttons {
+    fn default() -> Self {
+        use crate::gpio::button::Actions;
+        use crate::schemas::command::Command;
+        const BUTTON_POLL_INTERVAL: Duration = Duration::from_millis(10);
+        const BUTTON_DEBOUNCE: Duration = Duration::from_millis(30);
+        const BUTTON_LONG_PRESS: Duration = Duration::from_millis(800);
+        const BUTTON_DOUBLE_PRESS: Duration = Duration::from_millis(300);
+        Buttons {
+            poll_interval: BUTTON_POLL_INTERVAL,
+            debounce: BUTTON_DEBOUNCE,
+            long_press: BUTTON_LONG_PRESS,
+            double_press: BUTTON_DOUBLE_PRESS,
+            actions: [
+                Some(Actions {
+                    short: Some(Command::Tare),
+                    double: Some(Command::NextScreen),
+                    ..Default::default()
+                }),
+                Some(Actions {
+                    short: Some(Command::CycleDrink),
+                    double: Some(Command::PreviousScreen),
+                    ..Default::default()
+                }),
+                Some(Actions {
+                    short: Some(Command::StartShot),
+                    long: Some(Command::StopShot),
+                    ..Default::default()
+                }),
+                Some(Actions {
+                    short: Some(Command::ToggleSteam),
+                    ..Default::default()
+                }),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Favourite {
+    pub pressure: Bar,
+    pub weight: Grams,
+}
+impl Default for Favourite {
+    fn default() -> Self {
+        const FAVOURITE_PRESSURE: Bar = 9.0;
+        const FAVOURITE_WEIGHT: Grams = 36.0;
+        Favourite {
+            pressure: FAVOURITE_PRESSURE,
+            weight: FAVOURITE_WEIGHT,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone)]
 pub struct Indicator {
     pub refresh_interval: Duration,
     pub led_count: usize,
+    pub theme: crate::indicator::theme::Theme,
 }
 impl Default for Indicator {
     fn default() -> Self {
@@ -254,20 +397,92 @@ impl Default for Indicator {
         Indicator {
             led_count: LED_COUNT,
             refresh_interval: LED_REFRESH_INTERVAL,
+            theme: crate::indicator::theme::Theme::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Display {
+    pub refresh_interval: Duration,
+}
+impl Default for Display {
+    fn default() -> Self {
+        const DISPLAY_REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+        Display {
+            refresh_interval: DISPLAY_REFRESH_INTERVAL,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Telemetry {
+    pub sample_interval: Duration,
+    // Anything longer is cut off rather than filling memory
+    pub max_duration: Duration,
+    pub log_steam: bool,
+    // The usual dose, so brew ratios are known before a shot is annotated
+    pub dose: Option<Grams>,
+}
+impl Default for Telemetry {
+    fn default() -> Self {
+        const TELEMETRY_SAMPLE_INTERVAL: Duration = Duration::from_millis(200);
+        const TELEMETRY_MAX_DURATION: Duration = Duration::from_secs(180);
+        Telemetry {
+            sample_interval: TELEMETRY_SAMPLE_INTERVAL,
+            max_duration: TELEMETRY_MAX_DURATION,
+            log_steam: false,
+            dose: None,
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Copy, Clone)]
+pub struct Descale {
+    pub temperature: Temperature,
+    pub cycles: usize,
+    pub group_flush_time: Duration,
+    pub hot_water_flush_time: Duration,
+    pub soak_time: Duration,
+    pub rinse_cycles: usize,
+    // How much fresh water has to be in the tank before rinsing
+    pub refill_volume: Milliliters,
+}
+impl Default for Descale {
+    fn default() -> Self {
+        const DESCALE_TEMPERATURE: Temperature = 60.0;
+        const DESCALE_CYCLES: usize = 5;
+        const GROUP_FLUSH_TIME: Duration = Duration::from_secs(10);
+        const HOT_WATER_FLUSH_TIME: Duration = Duration::from_secs(10);
+        const SOAK_TIME: Duration = Duration::from_secs(5 * 60);
+        const RINSE_CYCLES: usize = 3;
+        const REFILL_VOLUME: Milliliters = 2000.0;
+        Descale {
+            temperature: DESCALE_TEMPERATURE,
+            cycles: DESCALE_CYCLES,
+            group_flush_time: GROUP_FLUSH_TIME,
+            hot_water_flush_time: HOT_WATER_FLUSH_TIME,
+            soak_time: SOAK_TIME,
+            rinse_cycles: RINSE_CYCLES,
+            refill_volume: REFILL_VOLUME,
+        }
+    }
+}
+
+impl Descale {
+    pub fn validate(&self, tank: &crate::sensors::tank::Geometry) -> Result<(), String> {
+        let capacity = tank.capacity();
+        if self.refill_volume > capacity {
+            return Err(format!(
+                "Refill volume {:.0}mL is more than the tank holds ({:.0}mL)",
+                self.refill_volume, capacity
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[cfg(feature = "simulate")]
 pub const TIME_DILATION_FACTOR: f32 = 0.01;
 #[cfg(not(feature = "simulate"))]
 pub const TIME_DILATION_FACTOR: f32 = 1.0;
-
-pub struct Shots {}
-
-impl Shots {
-    pub const MAX_SHOT_TEMPERATURE: f32 = 105.0;
-    pub const MIN_SHOT_TEMPERATURE: f32 = 00.0;
-    pub const MAX_SHOT_PRESSURE_BAR: f32 = 12.0;
-    pub const MIN_SHOT_PRESSURE_BAR: f32 = 3.0;
-}
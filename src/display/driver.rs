@@ -0,0 +1,133 @@
+use super::screens::{self, Model, Sample, Screen};
+use crate::config::Display as Config;
+use crate::sensors::reading::Sensor;
+use crate::sensors::registry::Registry;
+use crate::state_machines::system_fsm::SystemState;
+use embedded_hal::i2c::I2c;
+use ssd1306::{prelude::*, I2CDisplayInterface, Ssd1306};
+use std::sync::{
+    mpsc::{channel, Sender},
+    Arc, RwLock,
+};
+use std::time::Instant;
+
+pub enum Message {
+    Next,
+    Previous,
+    SetDrink(Option<String>),
+    SetSystemState(SystemState),
+}
+
+#[derive(Clone)]
+pub struct Display {
+    mailbox: Sender<Message>,
+}
+
+fn valid<T: Copy>(sensor: &Sensor<T>) -> Option<T> {
+    sensor.is_valid().then(|| sensor.get())
+}
+
+impl Display {
+    // Stands in for a display the board doesn't have, every message is dropped
+    pub fn absent() -> Self {
+        let (mailbox, _) = channel();
+        Self { mailbox }
+    }
+
+    pub fn next_screen(&self) {
+        let _ = self.mailbox.send(Message::Next);
+    }
+
+    pub fn previous_screen(&self) {
+        let _ = self.mailbox.send(Message::Previous);
+    }
+
+    pub fn set_drink(&self, name: Option<String>) {
+        let _ = self.mailbox.send(Message::SetDrink(name));
+    }
+
+    pub fn set_system_state(&self, state: SystemState) {
+        let _ = self.mailbox.send(Message::SetSystemState(state));
+    }
+
+    pub fn start<I2C>(
+        i2c: I2C,
+        address: u8,
+        sensors: Registry,
+        shot_started: Arc<RwLock<Option<Instant>>>,
+        config: Config,
+    ) -> Self
+    where
+        I2C: I2c + Send + 'static,
+    {
+        let (mailbox, rx) = channel();
+
+        std::thread::Builder::new()
+            .name("Display".to_string())
+            .spawn(move || {
+                let interface = I2CDisplayInterface::new_custom_address(i2c, address);
+                let mut driver =
+                    Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+                        .into_buffered_graphics_mode();
+                if let Err(e) = driver.init() {
+                    log::error!("Failed to initialise display: {:?}", e);
+                    return;
+                }
+
+                let mut screen = Screen::default();
+                let mut model = Model::default();
+                let mut shot = None;
+                loop {
+                    while let Ok(message) = rx.try_recv() {
+                        match message {
+                            Message::Next => screen = screen.next(),
+                            Message::Previous => screen = screen.previous(),
+                            Message::SetDrink(name) => model.drink = name,
+                            Message::SetSystemState(state) => {
+                                let alert = match state {
+                                    SystemState::Warning(_)
+                                    | SystemState::Error(_)
+                                    | SystemState::Panic(_) => Some(state.to_string()),
+                                    _ => None,
+                                };
+                                // Errors are worth interrupting whatever is on screen for
+                                if matches!(state, SystemState::Error(_) | SystemState::Panic(_))
+                                    && model.alert != alert
+                                {
+                                    screen = Screen::Alerts;
+                                }
+                                model.alert = alert;
+                            }
+                        }
+                    }
+
+                    model.temperature = valid(&sensors.temperature);
+                    model.pressure = valid(&sensors.pressure);
+                    model.weight = valid(&sensors.weight);
+
+                    let started = *shot_started.read().unwrap();
+                    if started.is_some() && started != shot {
+                        model.shot.clear();
+                        screen = Screen::Shot;
+                    }
+                    shot = started;
+                    if shot.is_some() {
+                        model.record(Sample {
+                            pressure: sensors.pressure.get(),
+                            weight: sensors.weight.get(),
+                        });
+                    }
+
+                    if let Err(e) = screens::draw(screen, &model, &mut driver) {
+                        log::error!("Failed to draw {:?}: {:?}", screen, e);
+                    } else if let Err(e) = driver.flush() {
+                        log::error!("Failed to update display: {:?}", e);
+                    }
+                    std::thread::sleep(config.refresh_interval);
+                }
+            })
+            .expect("Failed to spawn display thread");
+
+        Self { mailbox }
+    }
+}
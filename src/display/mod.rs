@@ -0,0 +1,3 @@
+pub mod driver;
+
+pub use rs_coffee_core::screens;
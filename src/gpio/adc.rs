@@ -1,47 +1,78 @@
-use core::borrow::Borrow;
 use esp_idf_svc::hal::{
-    adc::oneshot::{AdcChannelDriver, AdcDriver},
+    adc::oneshot::{config::AdcChannelConfig, AdcChannelDriver, AdcDriver},
     gpio::ADCPin,
+    peripheral::Peripheral,
 };
+use esp_idf_sys::EspError;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-enum AdcSimulation {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Channel {
     Temperature,
     Pressure,
 }
 
-pub struct Adc<
-    'a,
+pub trait AdcSource {
+    fn read_millivolts(&mut self, channel: Channel) -> Result<f32, String>;
+}
+
+pub struct OnChipAdc<'a, T, P>
+where
     T: ADCPin,
-    P: ADCPin,
-    M: Borrow<AdcDriver<'a, T::Adc>>,
-    N: Borrow<AdcDriver<'a, P::Adc>>,
-> {
-    temperature_probe: AdcChannelDriver<'a, T, M>,
-    pressure_probe: AdcChannelDriver<'a, P, N>,
-    poll_interval: Duration,
-    next_poll: Instant,
-    samples: Vec<(u16, u16)>,
-    samples_to_average: usize,
-    last_reading: (f64, f64),
+    P: ADCPin<Adc = T::Adc>,
+{
+    temperature_probe: AdcChannelDriver<'a, T, Arc<AdcDriver<'a, T::Adc>>>,
+    pressure_probe: AdcChannelDriver<'a, P, Arc<AdcDriver<'a, T::Adc>>>,
 }
 
-impl<'a, T, P, M, N> Adc<'a, T, P, M, N>
+impl<'a, T, P> OnChipAdc<'a, T, P>
 where
     T: ADCPin,
-    P: ADCPin,
-    M: Borrow<AdcDriver<'a, T::Adc>>,
-    N: Borrow<AdcDriver<'a, P::Adc>>,
+    P: ADCPin<Adc = T::Adc>,
 {
     pub fn new(
-        adc1: AdcChannelDriver<'a, T, M>,
-        adc2: AdcChannelDriver<'a, P, N>,
-        poll_interval: Duration,
-        samples: usize,
-    ) -> Self {
+        adc: impl Peripheral<P = T::Adc> + 'a,
+        temperature_pin: impl Peripheral<P = T> + 'a,
+        pressure_pin: impl Peripheral<P = P> + 'a,
+        channel_config: &AdcChannelConfig,
+    ) -> Result<Self, EspError> {
+        let adc = Arc::new(AdcDriver::new(adc)?);
+        Ok(Self {
+            temperature_probe: AdcChannelDriver::new(adc.clone(), temperature_pin, channel_config)?,
+            pressure_probe: AdcChannelDriver::new(adc, pressure_pin, channel_config)?,
+        })
+    }
+}
+
+impl<'a, T, P> AdcSource for OnChipAdc<'a, T, P>
+where
+    T: ADCPin,
+    P: ADCPin<Adc = T::Adc>,
+{
+    fn read_millivolts(&mut self, channel: Channel) -> Result<f32, String> {
+        match channel {
+            Channel::Temperature => self.temperature_probe.read(),
+            Channel::Pressure => self.pressure_probe.read(),
+        }
+        .map(|mv| mv as f32)
+        .map_err(|e| format!("Failed to read {:?}: {:?}", channel, e))
+    }
+}
+
+pub struct Adc<'a> {
+    source: Box<dyn AdcSource + 'a>,
+    poll_interval: Duration,
+    next_poll: Instant,
+    samples: Vec<(f32, f32)>,
+    samples_to_average: usize,
+    last_reading: (f64, f64),
+}
+
+impl<'a> Adc<'a> {
+    pub fn new(source: Box<dyn AdcSource + 'a>, poll_interval: Duration, samples: usize) -> Self {
         Self {
-            temperature_probe: adc1,
-            pressure_probe: adc2,
+            source,
             poll_interval,
             next_poll: Instant::now(),
             samples: Vec::new(),
@@ -51,21 +82,26 @@ where
     }
 
     pub fn read(&mut self) -> Option<(f64, f64)> {
-        let raw_temperature = self
-            .temperature_probe
-            .read()
-            .expect("Failed to read temperature");
-        let raw_pressure = self.pressure_probe.read().expect("Failed to read pressure");
+        let sample = self
+            .source
+            .read_millivolts(Channel::Temperature)
+            .and_then(|t| Ok((t, self.source.read_millivolts(Channel::Pressure)?)));
 
-        self.samples.push((raw_temperature, raw_pressure));
+        match sample {
+            Ok(sample) => self.samples.push(sample),
+            Err(e) => {
+                log::error!("{}", e);
+                return None;
+            }
+        }
 
         if self.samples.len() > self.samples_to_average {
-            let (average_temperature, average_pressure): (u32, u32) = self
-                .samples
-                .iter()
-                .fold((0, 0), |acc, (t, p)| (acc.0 + *t as u32, acc.1 + *p as u32));
-            let average_temperature_sample = average_temperature as f64 / self.samples.len() as f64;
-            let average_pressure_sample = average_pressure as f64 / self.samples.len() as f64;
+            let (average_temperature, average_pressure): (f64, f64) =
+                self.samples.iter().fold((0.0, 0.0), |acc, (t, p)| {
+                    (acc.0 + *t as f64, acc.1 + *p as f64)
+                });
+            let average_temperature_sample = average_temperature / self.samples.len() as f64;
+            let average_pressure_sample = average_pressure / self.samples.len() as f64;
 
             self.samples.clear();
 
@@ -0,0 +1,211 @@
+use crate::gpio::adc::{AdcSource, Channel};
+use embedded_hal::i2c::I2c;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const REGISTER_CONVERSION: u8 = 0x00;
+const REGISTER_CONFIG: u8 = 0x01;
+
+const CONFIG_START_SINGLE: u16 = 1 << 15;
+const CONFIG_MODE_SINGLE_SHOT: u16 = 1 << 8;
+const CONFIG_COMPARATOR_DISABLE: u16 = 0b11;
+
+const MAX_CONVERSION_POLLS: usize = 4;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Gain {
+    Fsr6_144V,
+    Fsr4_096V,
+    Fsr2_048V,
+    Fsr1_024V,
+    Fsr0_512V,
+    Fsr0_256V,
+}
+
+impl Gain {
+    fn bits(&self) -> u16 {
+        let pga = match self {
+            Gain::Fsr6_144V => 0b000,
+            Gain::Fsr4_096V => 0b001,
+            Gain::Fsr2_048V => 0b010,
+            Gain::Fsr1_024V => 0b011,
+            Gain::Fsr0_512V => 0b100,
+            Gain::Fsr0_256V => 0b101,
+        };
+        pga << 9
+    }
+
+    fn full_scale_millivolts(&self) -> f32 {
+        match self {
+            Gain::Fsr6_144V => 6144.0,
+            Gain::Fsr4_096V => 4096.0,
+            Gain::Fsr2_048V => 2048.0,
+            Gain::Fsr1_024V => 1024.0,
+            Gain::Fsr0_512V => 512.0,
+            Gain::Fsr0_256V => 256.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum DataRate {
+    Sps8,
+    Sps16,
+    Sps32,
+    Sps64,
+    Sps128,
+    Sps250,
+    Sps475,
+    Sps860,
+}
+
+impl DataRate {
+    fn bits(&self) -> u16 {
+        let dr = match self {
+            DataRate::Sps8 => 0b000,
+            DataRate::Sps16 => 0b001,
+            DataRate::Sps32 => 0b010,
+            DataRate::Sps64 => 0b011,
+            DataRate::Sps128 => 0b100,
+            DataRate::Sps250 => 0b101,
+            DataRate::Sps475 => 0b110,
+            DataRate::Sps860 => 0b111,
+        };
+        dr << 5
+    }
+
+    fn conversion_time(&self) -> Duration {
+        let sps = match self {
+            DataRate::Sps8 => 8,
+            DataRate::Sps16 => 16,
+            DataRate::Sps32 => 32,
+            DataRate::Sps64 => 64,
+            DataRate::Sps128 => 128,
+            DataRate::Sps250 => 250,
+            DataRate::Sps475 => 475,
+            DataRate::Sps860 => 860,
+        };
+        Duration::from_micros(1_000_000 / sps + 100)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Differential0And1,
+    Differential0And3,
+    Differential1And3,
+    Differential2And3,
+    Single0,
+    Single1,
+    Single2,
+    Single3,
+}
+
+impl Input {
+    fn bits(&self) -> u16 {
+        let mux = match self {
+            Input::Differential0And1 => 0b000,
+            Input::Differential0And3 => 0b001,
+            Input::Differential1And3 => 0b010,
+            Input::Differential2And3 => 0b011,
+            Input::Single0 => 0b100,
+            Input::Single1 => 0b101,
+            Input::Single2 => 0b110,
+            Input::Single3 => 0b111,
+        };
+        mux << 12
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ChannelConfig {
+    pub input: Input,
+    pub gain: Gain,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub address: u8,
+    pub data_rate: DataRate,
+    pub temperature: ChannelConfig,
+    pub pressure: ChannelConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        const ADDRESS: u8 = 0x48;
+        Config {
+            address: ADDRESS,
+            data_rate: DataRate::Sps128,
+            temperature: ChannelConfig {
+                input: Input::Differential0And1,
+                gain: Gain::Fsr2_048V,
+            },
+            pressure: ChannelConfig {
+                input: Input::Single2,
+                gain: Gain::Fsr4_096V,
+            },
+        }
+    }
+}
+
+pub struct Ads1115<I2C: I2c> {
+    i2c: I2C,
+    config: Config,
+}
+
+impl<I2C: I2c> Ads1115<I2C> {
+    pub fn new(i2c: I2C, config: Config) -> Self {
+        Self { i2c, config }
+    }
+
+    fn write_register(&mut self, register: u8, value: u16) -> Result<(), I2C::Error> {
+        let [high, low] = value.to_be_bytes();
+        self.i2c.write(self.config.address, &[register, high, low])
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u16, I2C::Error> {
+        let mut buffer = [0; 2];
+        self.i2c
+            .write_read(self.config.address, &[register], &mut buffer)?;
+        Ok(u16::from_be_bytes(buffer))
+    }
+
+    fn convert(&mut self, channel: ChannelConfig) -> Result<i16, String> {
+        let config = CONFIG_START_SINGLE
+            | channel.input.bits()
+            | channel.gain.bits()
+            | CONFIG_MODE_SINGLE_SHOT
+            | self.config.data_rate.bits()
+            | CONFIG_COMPARATOR_DISABLE;
+
+        self.write_register(REGISTER_CONFIG, config)
+            .map_err(|e| format!("Failed to start conversion: {:?}", e))?;
+
+        for _ in 0..MAX_CONVERSION_POLLS {
+            std::thread::sleep(self.config.data_rate.conversion_time());
+            let status = self
+                .read_register(REGISTER_CONFIG)
+                .map_err(|e| format!("Failed to read status: {:?}", e))?;
+            if status & CONFIG_START_SINGLE != 0 {
+                let raw = self
+                    .read_register(REGISTER_CONVERSION)
+                    .map_err(|e| format!("Failed to read conversion: {:?}", e))?;
+                return Ok(raw as i16);
+            }
+        }
+
+        Err("Conversion timed out".to_string())
+    }
+}
+
+impl<I2C: I2c> AdcSource for Ads1115<I2C> {
+    fn read_millivolts(&mut self, channel: Channel) -> Result<f32, String> {
+        let channel_config = match channel {
+            Channel::Temperature => self.config.temperature,
+            Channel::Pressure => self.config.pressure,
+        };
+        let raw = self.convert(channel_config)?;
+        Ok(raw as f32 * channel_config.gain.full_scale_millivolts() / 32768.0)
+    }
+}
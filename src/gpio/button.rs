@@ -1,34 +1,20 @@
-#![allow(dead_code)]
-use esp_idf_hal::gpio::{InterruptType, PinDriver, Pull};
+use crate::config::Buttons as Config;
+use crate::gpio::debounce::Debounced;
+use crate::schemas::command::Command;
+use esp_idf_hal::gpio::{AnyIOPin, PinDriver, Pull};
 use esp_idf_svc::hal::gpio::{Input, InputPin, OutputPin};
-use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Default, Copy, Clone)]
-pub enum ButtonState {
-    Pressed,
-    #[default]
-    Released,
-}
-
-impl ButtonState {
-    pub fn press(&mut self) {
-        *self = ButtonState::Pressed;
-    }
-
-    pub fn was_pressed(&mut self) -> bool {
-        let res = match self {
-            ButtonState::Pressed => true,
-            ButtonState::Released => false,
-        };
-
-        *self = ButtonState::Released;
-        res
-    }
-}
+pub const MAX_BUTTONS: usize = 4;
 
+// Polled rather than interrupt driven, the gesture timing only needs the poll interval and
+// a std mutex can't be taken from an ISR
 pub struct Button<'a, PD: InputPin> {
     pin: PinDriver<'a, PD, Input>,
-    state: Arc<Mutex<ButtonState>>,
+    inverted: bool,
+    level: Debounced,
 }
 
 impl<'a, PD> Button<'a, PD>
@@ -38,57 +24,153 @@ where
     pub fn new(pin: PD, inverted: Option<bool>) -> Self {
         let inverted = inverted.unwrap_or(false);
 
-        let mut button_pin = PinDriver::input(pin).expect("failed to get brew button pin driver");
-
-        let (pull, edge) = if inverted {
-            (Pull::Up, InterruptType::NegEdge)
-        } else {
-            (Pull::Down, InterruptType::PosEdge)
-        };
-
+        let mut button_pin = PinDriver::input(pin).expect("failed to get button pin driver");
+        let pull = if inverted { Pull::Up } else { Pull::Down };
         button_pin
             .set_pull(pull)
-            .expect("failed to configure brew button");
-        button_pin
-            .set_interrupt_type(edge)
-            .expect("failed to configure brew button interrupt");
-
-        let state = Arc::new(Mutex::new(ButtonState::Released));
-        let state_clone = state.clone();
-        unsafe {
-            button_pin
-                .subscribe(move || {
-                    state_clone.lock().unwrap().press();
-                })
-                .unwrap();
-        }
+            .expect("failed to configure button");
 
         Self {
             pin: button_pin,
-            state,
+            inverted,
+            level: Debounced::new(),
         }
     }
 
-    #[allow(dead_code)]
-    pub fn is_high(&self) -> bool {
-        self.pin.is_high()
+    // Only changes once the pin has settled, so contact bounce doesn't look like another press
+    pub fn is_pressed(&mut self, debounce: Duration) -> bool {
+        let pin_active = self.pin.is_high() != self.inverted;
+        self.level.update(pin_active, debounce);
+        self.level.is_active()
     }
+}
 
-    pub fn enable(&mut self) {
-        self.pin
-            .enable_interrupt()
-            .expect("failed to enable interrupt");
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Gesture {
+    Short,
+    Long,
+    Double,
+}
+
+impl std::fmt::Display for Gesture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let gesture = match self {
+            Self::Short => "Short press",
+            Self::Long => "Long press",
+            Self::Double => "Double press",
+        };
+        write!(f, "{}", gesture)
     }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+pub struct Actions {
+    pub short: Option<Command>,
+    pub long: Option<Command>,
+    pub double: Option<Command>,
+}
 
-    #[allow(dead_code)]
-    pub fn disable(&mut self) {
-        self.pin
-            .disable_interrupt()
-            .expect("failed to disable interrupt");
+impl Actions {
+    fn get(&self, gesture: Gesture) -> Option<Command> {
+        match gesture {
+            Gesture::Short => self.short,
+            Gesture::Long => self.long,
+            Gesture::Double => self.double,
+        }
     }
+}
+
+#[derive(Default)]
+struct GestureDetector {
+    pressed_at: Option<Instant>,
+    long_sent: bool,
+    // A short press that could still turn into a double press
+    released_at: Option<Instant>,
+}
+
+impl GestureDetector {
+    fn update(&mut self, pressed: bool, actions: &Actions, config: &Config) -> Option<Gesture> {
+        let now = Instant::now();
+        match (pressed, self.pressed_at) {
+            (true, None) => {
+                self.pressed_at = Some(now);
+                self.long_sent = false;
+                None
+            }
+            (true, Some(pressed_at)) => {
+                if self.long_sent || now - pressed_at < config.long_press {
+                    return None;
+                }
+                self.long_sent = true;
+                self.released_at = None;
+                Some(Gesture::Long)
+            }
+            (false, Some(_)) => {
+                self.pressed_at = None;
+                if self.long_sent {
+                    None
+                } else if self.released_at.take().is_some() {
+                    Some(Gesture::Double)
+                } else if actions.double.is_none() {
+                    // Nothing to wait for, so don't delay the short press
+                    Some(Gesture::Short)
+                } else {
+                    self.released_at = Some(now);
+                    None
+                }
+            }
+            (false, None) => match self.released_at {
+                Some(released_at) if now - released_at >= config.double_press => {
+                    self.released_at = None;
+                    Some(Gesture::Short)
+                }
+                _ => None,
+            },
+        }
+    }
+}
+
+pub struct Buttons;
+
+impl Buttons {
+    // Gestures are turned into commands and handed to the same executor REST and MQTT use
+    pub fn start(
+        pins: [Option<AnyIOPin>; MAX_BUTTONS],
+        active_low: bool,
+        commands: Sender<Command>,
+        config: Config,
+    ) {
+        std::thread::Builder::new()
+            .name("Buttons".to_string())
+            .spawn(move || {
+                let mut buttons: Vec<_> = pins
+                    .into_iter()
+                    .zip(config.actions)
+                    .enumerate()
+                    .filter_map(|(index, (pin, actions))| {
+                        let (pin, actions) = (pin?, actions?);
+                        let button = Button::new(pin, Some(active_low));
+                        Some((index, button, actions, GestureDetector::default()))
+                    })
+                    .collect();
 
-    pub fn was_pressed(&mut self) -> bool {
-        self.enable();
-        self.state.lock().unwrap().was_pressed()
+                loop {
+                    for (index, button, actions, detector) in buttons.iter_mut() {
+                        let pressed = button.is_pressed(config.debounce);
+                        let Some(gesture) = detector.update(pressed, actions, &config) else {
+                            continue;
+                        };
+                        log::info!("Button {}: {}", index, gesture);
+                        if let Some(command) = actions.get(gesture) {
+                            if commands.send(command).is_err() {
+                                log::error!("Command executor has gone away");
+                                return;
+                            }
+                        }
+                    }
+                    std::thread::sleep(config.poll_interval);
+                }
+            })
+            .expect("Failed to spawn button thread");
     }
 }
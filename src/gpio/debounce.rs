@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum SwitchState {
+    Active,
+    #[default]
+    Released,
+}
+
+// A polled pin level, used for the rocker switches and the front-panel buttons
+#[derive(Debug, Copy, Clone)]
+pub struct Debounced {
+    state: SwitchState,
+    candidate: bool,
+    candidate_since: Instant,
+    changed: Instant,
+}
+
+impl Debounced {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            state: SwitchState::Released,
+            candidate: false,
+            candidate_since: now,
+            changed: now,
+        }
+    }
+
+    // Only accept a new state once the pin has stayed at the new level for the debounce time
+    pub fn update(&mut self, pin_active: bool, debounce: Duration) -> Option<SwitchState> {
+        let now = Instant::now();
+        if pin_active != self.candidate {
+            self.candidate = pin_active;
+            self.candidate_since = now;
+            return None;
+        }
+
+        let state = if pin_active {
+            SwitchState::Active
+        } else {
+            SwitchState::Released
+        };
+        if state == self.state || now - self.candidate_since < debounce {
+            return None;
+        }
+
+        self.state = state;
+        self.changed = now;
+        Some(state)
+    }
+
+    pub fn active_for(&self) -> Option<Duration> {
+        match self.state {
+            SwitchState::Active => Some(self.changed.elapsed()),
+            SwitchState::Released => None,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.state == SwitchState::Active
+    }
+}
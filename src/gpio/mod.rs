@@ -1,5 +1,7 @@
 pub mod adc;
+pub mod ads1115;
 pub mod button;
+pub mod debounce;
 pub mod pwm;
 pub mod relay;
 pub mod switch;
@@ -1,74 +1,49 @@
+use crate::config::Switches as Config;
+use crate::gpio::debounce::{Debounced, SwitchState};
+use crate::schemas::event::EventBuffer;
 use crate::schemas::status::Switches as Report;
 use esp_idf_hal::gpio::{PinDriver, Pull};
 use esp_idf_svc::hal::gpio::{InputPin, OutputPin};
+use rs_coffee_core::switches::{resolve, Held};
+pub use rs_coffee_core::switches::{Mapping, SwitchesState, MAX_MAPPINGS};
 use std::{
-    sync::{Arc, RwLock},
+    sync::{Arc, Mutex, RwLock},
     thread,
 };
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
-enum SwitchState {
-    Active,
-    #[default]
-    Released,
-}
-
-impl SwitchState {
-    pub fn update(&self, state: bool) -> Option<Self> {
-        match (state, self) {
-            (true, Self::Released) => Some(Self::Active),
-            (false, Self::Active) => Some(Self::Released),
-            _ => None,
-        }
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub enum SwitchesState {
-    Idle,
+enum Switch {
     Brew,
     HotWater,
     Steam,
-    AutoTune,
-    Backflush,
 }
 
-impl std::fmt::Display for SwitchesState {
+impl std::fmt::Display for Switch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let state = match self {
-            Self::Idle => "Idle",
+        let switch = match self {
             Self::Brew => "Brew",
-            Self::HotWater => "Hot Water",
+            Self::HotWater => "Hot water",
             Self::Steam => "Steam",
-            Self::AutoTune => "Autotune",
-            Self::Backflush => "Backflush",
         };
-        write!(f, "{}", state)
-    }
-}
-
-impl SwitchesState {
-    fn update(&self, brew: SwitchState, hot_water: SwitchState, steam: SwitchState) -> Self {
-        match (brew, hot_water, steam) {
-            (SwitchState::Active, SwitchState::Active, SwitchState::Active) => Self::AutoTune,
-            (SwitchState::Active, SwitchState::Active, SwitchState::Released) => Self::Backflush,
-            (SwitchState::Active, _, _) => Self::Brew,
-            (_, SwitchState::Active, _) => Self::HotWater,
-            (_, _, SwitchState::Active) => Self::Steam,
-            _ => Self::Idle,
-        }
+        write!(f, "{}", switch)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Switches {
-    brew_switch_state: Arc<RwLock<SwitchState>>,
-    hot_water_switch_state: Arc<RwLock<SwitchState>>,
-    steam_switch_state: Arc<RwLock<SwitchState>>,
+    switches: Arc<RwLock<[Debounced; 3]>>,
+    state: Arc<RwLock<SwitchesState>>,
 }
 
 impl Switches {
-    pub fn new<PB, PH, PS>(brew_pin: PB, hot_water_pin: PH, steam_pin: PS) -> Self
+    pub fn new<PB, PH, PS>(
+        brew_pin: PB,
+        hot_water_pin: PH,
+        steam_pin: PS,
+        active_low: bool,
+        events: Arc<Mutex<EventBuffer>>,
+        config: Config,
+    ) -> Self
     where
         PB: InputPin + OutputPin,
         PH: InputPin + OutputPin,
@@ -81,66 +56,69 @@ impl Switches {
         let mut steam_switch_pin =
             PinDriver::input(steam_pin).expect("failed to get steam switch pin driver");
 
+        let pull = if active_low { Pull::Up } else { Pull::Down };
         brew_switch_pin
-            .set_pull(Pull::Up)
+            .set_pull(pull)
             .expect("failed to configure switch");
         hot_water_switch_pin
-            .set_pull(Pull::Up)
+            .set_pull(pull)
             .expect("failed to configure switch");
         steam_switch_pin
-            .set_pull(Pull::Up)
+            .set_pull(pull)
             .expect("failed to configure switch");
 
-        let brew_switch_state = Arc::new(RwLock::new(SwitchState::Released));
-        let hot_water_switch_state = Arc::new(RwLock::new(SwitchState::Released));
-        let steam_switch_state = Arc::new(RwLock::new(SwitchState::Released));
-
-        let brew_clone = brew_switch_state.clone();
-        let hot_water_clone = hot_water_switch_state.clone();
-        let steam_clone = steam_switch_state.clone();
+        let switches = Arc::new(RwLock::new([Debounced::new(); 3]));
+        let switches_clone = switches.clone();
+        let state = Arc::new(RwLock::new(SwitchesState::Idle));
+        let state_clone = state.clone();
 
         std::thread::spawn(move || loop {
-            let pin_state = brew_switch_pin.is_low();
-            let last_state = *brew_clone.read().unwrap();
-
-            if let Some(new_state) = last_state.update(pin_state) {
-                *brew_clone.write().unwrap() = new_state;
-            }
-
-            let pin_state = hot_water_switch_pin.is_low();
-            let last_state = *hot_water_clone.read().unwrap();
-            if let Some(new_state) = last_state.update(pin_state) {
-                *hot_water_clone.write().unwrap() = new_state;
+            let pins = [
+                (Switch::Brew, brew_switch_pin.is_low() == active_low),
+                (
+                    Switch::HotWater,
+                    hot_water_switch_pin.is_low() == active_low,
+                ),
+                (Switch::Steam, steam_switch_pin.is_low() == active_low),
+            ];
+
+            for (index, (switch, pin_active)) in pins.into_iter().enumerate() {
+                let changed =
+                    switches_clone.write().unwrap()[index].update(pin_active, config.debounce);
+                if let Some(state) = changed {
+                    let state = match state {
+                        SwitchState::Active => "on",
+                        SwitchState::Released => "off",
+                    };
+                    log::info!("{} switch {}", switch, state);
+                    events
+                        .lock()
+                        .unwrap()
+                        .info(module_path!(), format!("{} switch {}", switch, state));
+                }
             }
 
-            let pin_state = steam_switch_pin.is_low();
-            let last_state = *steam_clone.read().unwrap();
-            if let Some(new_state) = last_state.update(pin_state) {
-                *steam_clone.write().unwrap() = new_state;
-            }
-            thread::sleep(std::time::Duration::from_millis(100));
+            let held: Held = switches_clone
+                .read()
+                .unwrap()
+                .map(|switch| switch.active_for());
+            *state_clone.write().unwrap() = resolve(&config.mapping, &held);
+            thread::sleep(config.poll_interval);
         });
 
-        Self {
-            brew_switch_state,
-            hot_water_switch_state,
-            steam_switch_state,
-        }
+        Self { switches, state }
     }
 
     pub fn get_state(&self) -> SwitchesState {
-        let brew = *self.brew_switch_state.read().unwrap();
-        let hot_water = *self.hot_water_switch_state.read().unwrap();
-        let steam = *self.steam_switch_state.read().unwrap();
-
-        SwitchesState::Idle.update(brew, hot_water, steam)
+        *self.state.read().unwrap()
     }
 
     pub fn get_report(&self) -> Report {
+        let switches = self.switches.read().unwrap();
         Report {
-            brew: *self.brew_switch_state.read().unwrap() == SwitchState::Active,
-            water: *self.hot_water_switch_state.read().unwrap() == SwitchState::Active,
-            steam: *self.steam_switch_state.read().unwrap() == SwitchState::Active,
+            brew: switches[0].is_active(),
+            water: switches[1].is_active(),
+            steam: switches[2].is_active(),
         }
     }
 }
@@ -1 +1,4 @@
+pub mod onboard;
 pub mod ring;
+pub mod status;
+pub mod theme;
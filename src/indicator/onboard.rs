@@ -0,0 +1,92 @@
+use super::ring::{Ring, State};
+use crate::state_machines::system_fsm::SystemState;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Wifi {
+    #[default]
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Default, Copy, Clone)]
+struct Connectivity {
+    wifi: Wifi,
+    mqtt: bool,
+    ota: bool,
+}
+
+// Written to by whatever owns each connection, read by the status LED
+#[derive(Clone, Default)]
+pub struct Network {
+    state: Arc<RwLock<Connectivity>>,
+}
+
+impl Network {
+    pub fn set_wifi(&self, wifi: Wifi) {
+        self.state.write().unwrap().wifi = wifi;
+    }
+
+    pub fn set_mqtt(&self, connected: bool) {
+        self.state.write().unwrap().mqtt = connected;
+    }
+
+    // Nothing runs an OTA update yet, the updater should set this while it's writing
+    #[allow(dead_code)]
+    pub fn set_ota(&self, in_progress: bool) {
+        self.state.write().unwrap().ota = in_progress;
+    }
+}
+
+fn state_for(connectivity: Connectivity, system_state: &SystemState) -> State {
+    match (system_state, connectivity) {
+        (SystemState::Panic(_), _) => State::Panic,
+        (SystemState::Error(_), _) => State::Error,
+        (SystemState::Rebooting(_), _) => State::Busy,
+        (_, Connectivity { ota: true, .. }) => State::Updating,
+        (
+            _,
+            Connectivity {
+                wifi: Wifi::Connecting,
+                ..
+            },
+        ) => State::Connecting,
+        (
+            _,
+            Connectivity {
+                wifi: Wifi::Disconnected,
+                ..
+            },
+        ) => State::Offline,
+        (SystemState::Warning(_), _) => State::Warning,
+        (_, Connectivity { mqtt: false, .. }) => State::MqttDisconnected,
+        _ => State::Heartbeat,
+    }
+}
+
+pub struct StatusLed;
+
+impl StatusLed {
+    // Runs separately from the main loop so the LED keeps telling the truth when that stalls
+    pub fn start(led: Ring, network: Network, system_state: Arc<Mutex<SystemState>>) {
+        std::thread::Builder::new()
+            .name("Status LED".to_string())
+            .spawn(move || {
+                let mut shown = None;
+                loop {
+                    let connectivity = *network.state.read().unwrap();
+                    let state = state_for(connectivity, &system_state.lock().unwrap());
+                    if shown != Some(state) {
+                        led.set_state(state);
+                        shown = Some(state);
+                    }
+                    std::thread::sleep(UPDATE_INTERVAL);
+                }
+            })
+            .expect("Failed to spawn status LED thread");
+    }
+}
@@ -1,3 +1,5 @@
+use super::theme::{Gradient, PressureColours, Theme};
+use crate::types::Bar;
 use esp_idf_hal::{gpio::OutputPin, peripheral::Peripheral, rmt::RmtChannel};
 use smart_led_effects::{
     strip::{self, EffectIterator},
@@ -5,14 +7,12 @@ use smart_led_effects::{
 };
 use smart_leds_trait::SmartLedsWrite;
 use std::sync::mpsc::{channel, Sender};
-use std::time::Duration;
 use ws2812_esp32_rmt_driver::{Ws2812Esp32Rmt, RGB8 as Rgb};
 
 #[derive(Debug, PartialEq, Clone, Copy, std::default::Default)]
 pub enum State {
     #[default]
     Off,
-    #[allow(dead_code)]
     Temperature {
         min: f32,
         max: f32,
@@ -25,63 +25,125 @@ pub enum State {
     },
     Idle,
     Busy,
+    // Empties as the boiler closes in on the setpoint
+    Heating {
+        seconds_to_ready: u32,
+    },
+    Ready,
+    Shot {
+        percentage: f32,
+        pressure: Bar,
+    },
+    LowWater,
+    Connecting,
+    Offline,
+    MqttDisconnected,
+    Updating,
+    Warning,
     Panic,
     Error,
     Heartbeat,
 }
 
+// The ring is full when the boiler is this far from ready
+const HEATING_FULL_SCALE: f32 = 600.0;
+// Below this the puck is still filling or the grind is too coarse, above the upper limit
+// it's choking
+const PRESSURE_LOW: Bar = 6.0;
+const PRESSURE_HIGH: Bar = 10.0;
+
+fn pressure_colour(pressure: Bar, colours: &PressureColours) -> Srgb<f32> {
+    let colour = if pressure < PRESSURE_LOW {
+        colours.low
+    } else if pressure > PRESSURE_HIGH {
+        colours.high
+    } else {
+        colours.good
+    };
+    colour.as_srgb_f32()
+}
+
+fn progress_bar(
+    count: usize,
+    gradient: &Gradient,
+    min: f32,
+    max: f32,
+    level: f32,
+) -> Box<dyn EffectIterator> {
+    let mut progress = strip::ProgressBar::new(
+        count,
+        Some(gradient.empty.as_srgb_f32()),
+        Some(gradient.full.as_srgb_f32()),
+        Some(gradient.empty != gradient.full),
+    );
+
+    let percentage: f32 = level / (max - min) * 100.0;
+    progress.set_percentage(percentage);
+
+    Box::new(progress)
+}
+
 impl State {
-    pub fn as_effect(&self, count: usize) -> Box<dyn EffectIterator> {
+    pub fn as_effect(&self, count: usize, theme: &Theme) -> Box<dyn EffectIterator> {
         match self {
-            State::Panic => Box::new(strip::Strobe::new(
-                count,
-                Some(Srgb::new(255, 0, 0)),
-                Duration::from_millis(100),
-                None,
-            )),
-            State::Error => Box::new(strip::Cylon::new(count, Srgb::new(255, 0, 0), None, None)),
-            State::Busy => Box::new(strip::RunningLights::new(count, None, false)),
-            State::Idle => Box::new(strip::Breathe::new(count, None, None)),
+            State::Panic => theme.panic.as_effect(count),
+            State::Error => theme.error.as_effect(count),
+            State::Busy => theme.busy.as_effect(count),
+            State::Idle | State::Off => theme.idle.as_effect(count),
             State::Guage { min, max, level } => {
-                let mut progress = strip::ProgressBar::new(
-                    count,
-                    Some(Srgb::new(0.0, 1.0, 0.0)),
-                    Some(Srgb::new(1.0, 0.0, 0.0)),
-                    Some(false),
-                );
-
-                let percentage: f32 = level / (max - min) * 100.0;
-                progress.set_percentage(percentage);
-
-                Box::new(progress)
+                progress_bar(count, &theme.guage, *min, *max, *level)
             }
             State::Temperature { min, max, level } => {
-                let mut progress = strip::ProgressBar::new(
-                    count,
-                    Some(Srgb::new(0.0, 0.0, 1.0)),
-                    Some(Srgb::new(1.0, 0.0, 0.0)),
-                    Some(true),
-                );
-
-                let percentage: f32 = level / (max - min) * 100.0;
-                progress.set_percentage(percentage);
+                progress_bar(count, &theme.temperature, *min, *max, *level)
+            }
+            State::Heating { seconds_to_ready } => {
+                let colour = Gradient {
+                    empty: theme.heating,
+                    full: theme.heating,
+                };
+                let seconds = (*seconds_to_ready as f32).min(HEATING_FULL_SCALE);
+                progress_bar(count, &colour, 0.0, HEATING_FULL_SCALE, seconds)
+            }
+            State::Ready => theme.ready.as_effect(count),
+            State::Shot {
+                percentage,
+                pressure,
+            } => {
+                let colour = pressure_colour(*pressure, &theme.shot);
+                let mut progress =
+                    strip::ProgressBar::new(count, Some(colour), Some(colour), Some(false));
+                progress.set_percentage(percentage.min(100.0));
 
                 Box::new(progress)
             }
-            State::Heartbeat => Box::new(strip::Rainbow::new(1, None)),
-            State::Off => Box::new(strip::Breathe::new(count, None, None)),
+            State::LowWater => theme.low_water.as_effect(count),
+            State::Heartbeat => theme.heartbeat.as_effect(1),
+            State::Connecting => theme.connecting.as_effect(count),
+            State::Offline => theme.offline.as_effect(count),
+            State::MqttDisconnected => theme.mqtt_disconnected.as_effect(count),
+            State::Updating => theme.updating.as_effect(count),
+            State::Warning => theme.warning.as_effect(count),
         }
     }
 }
 
+pub enum Message {
+    SetState(State),
+    SetTheme(Theme),
+}
+
 #[derive(Clone)]
 pub struct Ring {
-    mailbox: Sender<State>,
+    mailbox: Sender<Message>,
 }
 
 impl Ring {
     pub fn set_state(&self, state: State) {
-        self.mailbox.send(state).unwrap();
+        self.mailbox.send(Message::SetState(state)).unwrap();
+    }
+
+    pub fn set_theme(&self, theme: Theme) {
+        self.mailbox.send(Message::SetTheme(theme)).unwrap();
     }
 
     pub fn new<C: RmtChannel>(
@@ -89,31 +151,43 @@ impl Ring {
         pin: impl Peripheral<P = impl OutputPin> + 'static,
         tickspeed: std::time::Duration,
         count: usize,
+        theme: Theme,
     ) -> Self {
         let mut led = Ws2812Esp32Rmt::new(rmt_channel, pin).expect("Failed to initialize LED ring");
-        let (tx, rx) = channel::<State>();
+        let (tx, rx) = channel::<Message>();
 
         std::thread::spawn(move || {
             let mut active_state = State::Off;
+            let mut theme = theme;
             let mut effect: Box<dyn EffectIterator> = Box::new(strip::Rainbow::new(count, None));
             log::info!("Starting indicator thread");
             loop {
-                while let Ok(state) = rx.try_recv() {
-                    if state != active_state {
-                        log::debug!("Setting indicator state: {:?}", state);
-                        effect = state.as_effect(count);
-                        active_state = state;
+                while let Ok(message) = rx.try_recv() {
+                    match message {
+                        Message::SetState(state) if state != active_state => {
+                            log::debug!("Setting indicator state: {:?}", state);
+                            effect = state.as_effect(count, &theme);
+                            active_state = state;
+                        }
+                        Message::SetState(_) => {}
+                        Message::SetTheme(new_theme) => {
+                            log::info!("Setting indicator theme");
+                            theme = new_theme;
+                            effect = active_state.as_effect(count, &theme);
+                        }
                     }
                 }
 
+                let brightness = theme.current_brightness();
+
                 let pixels: Vec<Rgb> = effect
                     .next()
                     .unwrap()
                     .iter()
                     .map(|i| Rgb {
-                        r: i.red,
-                        g: i.green,
-                        b: i.blue,
+                        r: (i.red as f32 * brightness) as u8,
+                        g: (i.green as f32 * brightness) as u8,
+                        b: (i.blue as f32 * brightness) as u8,
                     })
                     .collect();
                 led.write(pixels).unwrap();
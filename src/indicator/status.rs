@@ -0,0 +1,59 @@
+use super::ring::State;
+use crate::components::boiler::Readiness;
+use crate::state_machines::operational_fsm::OperationalState;
+use crate::types::{Bar, Grams, Temperature};
+
+// Everything the ring shows, sampled once per pass of the main loop
+pub struct Snapshot {
+    pub readiness: Readiness,
+    pub boiler_temperature: Temperature,
+    pub pressure: Bar,
+    pub weight: Grams,
+    pub low_water: bool,
+}
+
+impl Snapshot {
+    pub fn state(&self, operational_state: &OperationalState) -> State {
+        match operational_state {
+            OperationalState::Idle | OperationalState::Brewing(_) | OperationalState::Steaming
+                if self.low_water =>
+            {
+                State::LowWater
+            }
+            OperationalState::Idle => self.readiness_state(25.0, 100.0),
+            OperationalState::Brewing(Some(target)) if *target > 0.0 => State::Shot {
+                percentage: self.weight / target * 100.0,
+                pressure: self.pressure,
+            },
+            OperationalState::Brewing(_) => State::Temperature {
+                min: 25.0,
+                max: 100.0,
+                level: self.boiler_temperature,
+            },
+            OperationalState::Steaming => self.readiness_state(25.0, 140.0),
+            OperationalState::Descaling(progress) => State::Guage {
+                min: 0.0,
+                max: 100.0,
+                level: progress.percentage,
+            },
+            OperationalState::StartingUp(_)
+            | OperationalState::AutoTuneInit
+            | OperationalState::AutoTuning => State::Busy,
+        }
+    }
+
+    fn readiness_state(&self, min: Temperature, max: Temperature) -> State {
+        match self.readiness {
+            Readiness::Off => State::Idle,
+            Readiness::Heating(Some(time)) => State::Heating {
+                seconds_to_ready: time.as_secs() as u32,
+            },
+            Readiness::Heating(None) => State::Temperature {
+                min,
+                max,
+                level: self.boiler_temperature,
+            },
+            Readiness::Ready => State::Ready,
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use smart_led_effects::{
+    strip::{self, EffectIterator},
+    Srgb,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Anything earlier and SNTP hasn't set the clock yet
+pub const CLOCK_SET_AFTER: u64 = 1_700_000_000;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Colour {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    pub fn as_srgb(&self) -> Srgb<u8> {
+        Srgb::new(self.r, self.g, self.b)
+    }
+
+    pub fn as_srgb_f32(&self) -> Srgb<f32> {
+        self.as_srgb().into_format()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Effect {
+    Solid,
+    Breathe,
+    Strobe(Duration),
+    Cylon,
+    RunningLights,
+    Rainbow,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Style {
+    pub effect: Effect,
+    pub colour: Colour,
+}
+
+impl Style {
+    const fn new(effect: Effect, colour: Colour) -> Self {
+        Self { effect, colour }
+    }
+
+    pub fn as_effect(&self, count: usize) -> Box<dyn EffectIterator> {
+        match self.effect {
+            Effect::Solid => {
+                let colour = self.colour.as_srgb_f32();
+                let mut solid =
+                    strip::ProgressBar::new(count, Some(colour), Some(colour), Some(false));
+                solid.set_percentage(100.0);
+                Box::new(solid)
+            }
+            Effect::Breathe => Box::new(strip::Breathe::new(
+                count,
+                Some(self.colour.as_srgb()),
+                None,
+            )),
+            Effect::Strobe(period) => Box::new(strip::Strobe::new(
+                count,
+                Some(self.colour.as_srgb()),
+                period,
+                None,
+            )),
+            Effect::Cylon => Box::new(strip::Cylon::new(count, self.colour.as_srgb(), None, None)),
+            Effect::RunningLights => Box::new(strip::RunningLights::new(
+                count,
+                Some(self.colour.as_srgb()),
+                false,
+            )),
+            Effect::Rainbow => Box::new(strip::Rainbow::new(count, None)),
+        }
+    }
+}
+
+// A progress bar fades from `empty` to `full` along its length
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Gradient {
+    pub empty: Colour,
+    pub full: Colour,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct PressureColours {
+    pub low: Colour,
+    pub good: Colour,
+    pub high: Colour,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct TimeOfDay {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TimeOfDay {
+    fn minutes(&self) -> u32 {
+        self.hour as u32 * 60 + self.minute as u32
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Night {
+    pub start: TimeOfDay,
+    pub end: TimeOfDay,
+    // Scales the global brightness overnight, zero turns the ring off
+    pub brightness: f32,
+    pub utc_offset_minutes: i16,
+}
+
+impl Night {
+    fn is_active(&self) -> bool {
+        let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+            return false;
+        };
+        if now.as_secs() < CLOCK_SET_AFTER {
+            return false;
+        }
+
+        let local = now.as_secs() as i64 / 60 + self.utc_offset_minutes as i64;
+        let minute_of_day = local.rem_euclid(24 * 60) as u32;
+        let (start, end) = (self.start.minutes(), self.end.minutes());
+        if start <= end {
+            (start..end).contains(&minute_of_day)
+        } else {
+            // Runs past midnight
+            minute_of_day >= start || minute_of_day < end
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Theme {
+    pub brightness: f32,
+    pub night: Option<Night>,
+    pub idle: Style,
+    pub busy: Style,
+    pub ready: Style,
+    pub low_water: Style,
+    pub error: Style,
+    pub panic: Style,
+    pub heartbeat: Style,
+    pub connecting: Style,
+    pub offline: Style,
+    pub mqtt_disconnected: Style,
+    pub updating: Style,
+    pub warning: Style,
+    pub heating: Colour,
+    pub temperature: Gradient,
+    pub guage: Gradient,
+    pub shot: PressureColours,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        const RED: Colour = Colour::new(255, 0, 0);
+        const GREEN: Colour = Colour::new(0, 255, 0);
+        const BLUE: Colour = Colour::new(0, 0, 255);
+        const ORANGE: Colour = Colour::new(255, 128, 0);
+        const WHITE: Colour = Colour::new(255, 255, 255);
+        const CYAN: Colour = Colour::new(0, 255, 255);
+        const PURPLE: Colour = Colour::new(128, 0, 255);
+        Theme {
+            brightness: 1.0,
+            night: None,
+            idle: Style::new(Effect::Breathe, WHITE),
+            busy: Style::new(Effect::RunningLights, WHITE),
+            ready: Style::new(Effect::Breathe, GREEN),
+            low_water: Style::new(Effect::Strobe(Duration::from_millis(500)), BLUE),
+            error: Style::new(Effect::Cylon, RED),
+            panic: Style::new(Effect::Strobe(Duration::from_millis(100)), RED),
+            heartbeat: Style::new(Effect::Rainbow, WHITE),
+            connecting: Style::new(Effect::Breathe, BLUE),
+            offline: Style::new(Effect::Strobe(Duration::from_millis(1000)), BLUE),
+            mqtt_disconnected: Style::new(Effect::Breathe, CYAN),
+            updating: Style::new(Effect::Strobe(Duration::from_millis(250)), PURPLE),
+            warning: Style::new(Effect::Breathe, ORANGE),
+            heating: ORANGE,
+            temperature: Gradient {
+                empty: BLUE,
+                full: RED,
+            },
+            guage: Gradient {
+                empty: GREEN,
+                full: RED,
+            },
+            shot: PressureColours {
+                low: BLUE,
+                good: GREEN,
+                high: RED,
+            },
+        }
+    }
+}
+
+impl Theme {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.brightness) {
+            return Err("Brightness must be between 0 and 1".to_string());
+        }
+        if let Some(night) = self.night {
+            if !(0.0..=1.0).contains(&night.brightness) {
+                return Err("Night brightness must be between 0 and 1".to_string());
+            }
+            for time in [night.start, night.end] {
+                if time.hour > 23 || time.minute > 59 {
+                    return Err(format!("{}:{:02} is not a time", time.hour, time.minute));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // What every channel is scaled by right now, taking night mode into account
+    pub fn current_brightness(&self) -> f32 {
+        match self.night {
+            Some(night) if night.is_active() => self.brightness * night.brightness,
+            _ => self.brightness,
+        }
+    }
+}
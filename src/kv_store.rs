@@ -1,9 +1,10 @@
 use crate::config::Config;
+use crate::maintenance::Maintenance;
 use esp_idf_svc::nvs::*;
 use esp_idf_sys::EspError;
 use postcard::{from_bytes, to_vec};
 
-const MAX_VALUE_SIZE: usize = 256;
+const MAX_VALUE_SIZE: usize = 1024;
 
 #[derive(Debug)]
 pub enum Error {
@@ -25,16 +26,19 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {}
 pub enum File {
     Config(Config),
+    Maintenance(Maintenance),
 }
 
 pub enum FileType {
     Config,
+    Maintenance,
 }
 
 impl From<&File> for FileType {
     fn from(file: &File) -> Self {
         match file {
             File::Config(_) => FileType::Config,
+            File::Maintenance(_) => FileType::Maintenance,
         }
     }
 }
@@ -43,6 +47,7 @@ impl FileType {
     fn key(&self) -> String {
         match self {
             FileType::Config => "config".to_string(),
+            FileType::Maintenance => "maintenance".to_string(),
         }
     }
     pub fn load(&self, fs: &KeyValueStore) -> Result<File, Error> {
@@ -54,6 +59,11 @@ impl FileType {
                 .get_raw(&self.key(), value_buffer)
                 .map_err(Error::EspSys)?
                 .map(|val| File::Config(from_bytes::<Config>(val).unwrap_or_default())),
+            FileType::Maintenance => fs
+                .storage
+                .get_raw(&self.key(), value_buffer)
+                .map_err(Error::EspSys)?
+                .map(|val| File::Maintenance(from_bytes::<Maintenance>(val).unwrap_or_default())),
         }
         .ok_or(Error::NotFound(self.key()))
     }
@@ -70,6 +80,9 @@ impl File {
             File::Config(config) => {
                 to_vec::<Config, MAX_VALUE_SIZE>(config).map_err(Error::Serialize)?
             }
+            File::Maintenance(maintenance) => {
+                to_vec::<Maintenance, MAX_VALUE_SIZE>(maintenance).map_err(Error::Serialize)?
+            }
         };
 
         fs.storage
@@ -4,11 +4,14 @@
 mod api;
 mod app_state;
 mod board;
+mod board_profile;
 mod components;
 mod config;
+mod display;
 mod gpio;
 mod indicator;
 mod kv_store;
+mod maintenance;
 mod models;
 mod schemas;
 mod sensors;
@@ -18,7 +21,7 @@ use crate::components::boiler::Message as BoilerMessage;
 use anyhow::Result;
 use app_state::System;
 use gpio::switch::SwitchesState;
-use state_machines::operational_fsm::OperationalState;
+use state_machines::operational_fsm::{OperationalState, Transitions};
 use state_machines::system_fsm::{SystemState, Transition as SystemTransition};
 use std::thread;
 use std::time::Duration;
@@ -27,7 +30,7 @@ use std::time::Duration;
 const SIMULATE_AUTO_TUNE: bool = false;
 #[cfg(feature = "simulate")]
 fn simulate_auto_tuner(
-    temperature_probe: Arc<RwLock<f32>>,
+    temperature_probe: sensors::reading::Sensor<types::Temperature>,
     boiler: crate::components::boiler::Boiler,
 ) {
     if SIMULATE_AUTO_TUNE {
@@ -39,7 +42,7 @@ fn simulate_auto_tuner(
         auto_tuner.boiler = Some(boiler.clone());
         match auto_tuner.auto_tune_blocking() {
             Ok(res) => {
-                let probe_temperature = *temperature_probe.read().unwrap();
+                let probe_temperature = temperature_probe.get();
                 let message = components::boiler::Message::UpdateParameters {
                     parameters: res,
                     initial_probe_temperature: probe_temperature,
@@ -85,8 +88,8 @@ fn main() -> Result<()> {
     let config_mqtt = system.config.read().unwrap().mqtt.clone();
     api::mqtt::mqtt_create(config_mqtt, &system);
 
-    let temperature_probe = system.board.temperature.clone();
-    let ambient_probe = system.board.ambient_temperature.clone();
+    let temperature_probe = system.board.sensors.temperature.clone();
+    let ambient_probe = system.board.sensors.ambient.clone();
     let boiler = system.board.boiler.clone();
 
     #[cfg(feature = "simulate")]
@@ -111,48 +114,60 @@ fn main() -> Result<()> {
 
     let scale = system.board.scale.clone();
     let switches = system.board.switches.clone();
-    let pressure_probe = system.board.pressure.clone();
+    let pressure_probe = system.board.sensors.pressure.clone();
+    let level_probe = system.board.sensors.level.clone();
+    let weight_probe = system.board.sensors.weight.clone();
     let pump = system.board.pump.clone();
     let board = system.board.clone();
-    let level = system.board.level_sensor.clone();
 
     let mut previous_switch_state = SwitchesState::Idle;
 
     loop {
+        let health_change = {
+            let mut state = system.system_state.lock().unwrap();
+            system.board.health.update_system_state(&mut state)
+        };
+        match health_change {
+            Ok(Some(message)) => {
+                log::warn!("Sensor health changed: {}", message);
+                warn!(system, "Sensor health changed: {}", message);
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to apply sensor health: {:?}", e),
+        }
+
         let system_state = system.system_state.lock().unwrap().clone();
+        board.display.set_system_state(system_state.clone());
         let operational_state = system.operational_state.lock().unwrap().clone();
 
         match (system_state, operational_state) {
-            (SystemState::Healthy, operational_state) => {
-                let boiler_temperature = *temperature_probe.read().unwrap();
-                let pump_pressure = *pressure_probe.read().unwrap();
-                let ambient_temperature = *ambient_probe.read().unwrap();
+            (SystemState::Healthy | SystemState::Warning(_), operational_state) => {
+                let boiler_temperature = temperature_probe.get();
+                let pump_pressure = pressure_probe.get();
+                let ambient_temperature = ambient_probe.get();
+
+                let level_config = system.config.read().unwrap().level_sensor;
+                let snapshot = indicator::status::Snapshot {
+                    readiness: boiler.get_readiness(),
+                    boiler_temperature,
+                    pressure: pump_pressure,
+                    weight: weight_probe.get(),
+                    low_water: level_probe.is_valid()
+                        && level_config.tank.volume(level_probe.get())
+                            < level_config.low_level_threshold,
+                };
+                board
+                    .indicator
+                    .set_state(snapshot.state(&operational_state));
 
                 match operational_state {
                     OperationalState::Idle => {
                         log::debug!("Ambient temperature: {:.4}", ambient_temperature);
                         log::debug!("Boiler temperature: {:.4}", boiler_temperature);
                         log::debug!("Pump pressure: {:.2}", pump_pressure);
-                        log::debug!("Weight: {:.2}", scale.get_weight());
+                        log::debug!("Weight: {:.2}", weight_probe.get());
                         log::debug!("Flow: {:.2}", scale.get_flow());
-                        log::debug!("Level: {}", *level.distance.read().unwrap());
-                        board.indicator.set_state(indicator::ring::State::Idle);
-                    }
-                    OperationalState::Brewing => {
-                        let indicator = indicator::ring::State::Temperature {
-                            min: 25.0,
-                            max: 100.0,
-                            level: boiler_temperature,
-                        };
-                        board.indicator.set_state(indicator);
-                    }
-                    OperationalState::Steaming => {
-                        let indicator = indicator::ring::State::Temperature {
-                            min: 25.0,
-                            max: 140.0,
-                            level: boiler_temperature,
-                        };
-                        board.indicator.set_state(indicator);
+                        log::debug!("Level: {}", level_probe.get());
                     }
                     OperationalState::AutoTuneInit => {
                         log::info!("Auto-tuning boiler");
@@ -164,9 +179,7 @@ fn main() -> Result<()> {
                             .operational_state
                             .lock()
                             .unwrap()
-                            .transition(
-                                crate::state_machines::operational_fsm::Transitions::StartAutoTune,
-                            )
+                            .transition(Transitions::StartAutoTune)
                             .expect("Invalid transition :(");
 
                         #[cfg(feature = "simulate")]
@@ -197,11 +210,11 @@ fn main() -> Result<()> {
                             boiler.send_message(message);
 
                             system
-                                    .operational_state
-                                    .lock()
-                                    .unwrap()
-                                    .transition(crate::state_machines::operational_fsm::Transitions::AutoTuneComplete)
-                                    .expect("Invalid transition :(");
+                                .operational_state
+                                .lock()
+                                .unwrap()
+                                .transition(Transitions::AutoTuneComplete)
+                                .expect("Invalid transition :(");
                             loop_interval = Duration::from_millis(1000);
                         }
                     }
@@ -231,29 +244,56 @@ fn main() -> Result<()> {
         }
 
         let current_state = switches.get_state();
-        let level_sensor_max = system
-            .config
-            .read()
-            .unwrap()
-            .level_sensor
-            .low_level_threshold;
+        let favourite = system.config.read().unwrap().switches.favourite;
+
+        if previous_switch_state != current_state
+            && matches!(
+                *system.operational_state.lock().unwrap(),
+                OperationalState::Descaling(_)
+            )
+        {
+            log::warn!("Ignoring switches while descaling");
+            previous_switch_state = current_state;
+        }
 
         if previous_switch_state != current_state {
-            if previous_switch_state == SwitchesState::Brew {
+            // Same as commands, with the system in error the switches can only turn things off
+            let faulted = current_state != SwitchesState::Idle && system.in_error_state();
+            let mut refused = false;
+            *system.steam.write().unwrap() = current_state == SwitchesState::Steam && !faulted;
+            if matches!(
+                previous_switch_state,
+                SwitchesState::Brew | SwitchesState::Favourite
+            ) {
                 system.board.scale.stop_brewing();
             }
             match current_state {
+                _ if faulted => {
+                    boiler.send_message(BoilerMessage::SetMode(components::boiler::Mode::Off));
+                    pump.turn_off();
+                    log::warn!("Switched to {}, refused in the error state", current_state);
+                    warn!(
+                        system,
+                        "Switched to {}, refused in the error state", current_state
+                    );
+                }
                 SwitchesState::Idle => {
                     log::info!("Switched to idle");
                     info!(system, "Switched to idle");
                     boiler.send_message(BoilerMessage::SetMode(components::boiler::Mode::Off));
                     pump.turn_off();
                 }
-                SwitchesState::Brew => {
-                    level.send_message(sensors::a02yyuw::Message::DoRead);
-                    std::thread::sleep(Duration::from_millis(400));
-                    if *level.distance.read().unwrap() >= level_sensor_max {
-                        log::warn!("Threshold too low to brew");
+                SwitchesState::Brew | SwitchesState::Favourite => {
+                    if let Some(volume) = system.water_too_low() {
+                        log::warn!("Water level too low to brew: {:.0}mL", volume);
+                        refused = true;
+                    } else if current_state == SwitchesState::Favourite {
+                        info!(system, "Switched to favourite");
+                        log::info!("Switched to favourite");
+                        system.board.scale.start_brew();
+                        pump.turn_on_for_yield(favourite.pressure, favourite.weight);
+                        let mode = components::boiler::Mode::Mpc { target: 94.0 };
+                        boiler.send_message(BoilerMessage::SetMode(mode));
                     } else {
                         info!(system, "Switched to brew");
                         log::info!("Switched to brew");
@@ -290,14 +330,41 @@ fn main() -> Result<()> {
                     log::info!("Switched to auto-tune");
                     info!(system, "Switched to auto-tune");
                     pump.turn_off();
-                    if let Err(e) = system.operational_state.lock().unwrap().transition(
-                        crate::state_machines::operational_fsm::Transitions::StartAutoTune,
-                    ) {
+                    if let Err(e) = system
+                        .operational_state
+                        .lock()
+                        .unwrap()
+                        .transition(Transitions::StartAutoTune)
+                    {
                         log::warn!("Failed to transition to auto-tune: {:?}", e);
                         warn!(system, "Failed to transition to auto-tune: {:?}", e);
                     }
                 }
             }
+
+            let transition = match current_state {
+                // Whatever was running has been turned off
+                _ if faulted => Some(Transitions::Stop),
+                // The pump never started, there's no shot to follow
+                SwitchesState::Brew | SwitchesState::Favourite if refused => None,
+                SwitchesState::Brew => Some(Transitions::StartBrewing(None)),
+                SwitchesState::Favourite => Some(Transitions::StartBrewing(Some(favourite.weight))),
+                SwitchesState::Steam => Some(Transitions::StartSteaming),
+                SwitchesState::Idle | SwitchesState::HotWater | SwitchesState::Backflush => {
+                    Some(Transitions::Stop)
+                }
+                SwitchesState::AutoTune => None,
+            };
+            if let Some(transition) = transition {
+                if let Err(e) = system
+                    .operational_state
+                    .lock()
+                    .unwrap()
+                    .transition(transition)
+                {
+                    log::warn!("Switches ignored by the operational state: {:?}", e);
+                }
+            }
             previous_switch_state = current_state;
         }
         thread::sleep(loop_interval);
@@ -0,0 +1,70 @@
+use crate::indicator::theme::CLOCK_SET_AFTER;
+use crate::kv_store::*;
+use esp_idf_svc::nvs::*;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Maintenance {
+    pub last_descale: Option<u64>,
+    pub valve_actuations: u64,
+
+    #[serde(skip)]
+    pub nvs: Option<EspDefaultNvsPartition>,
+}
+
+impl Maintenance {
+    pub fn load_or_default(nvs: &Option<EspDefaultNvsPartition>) -> Self {
+        match Self::try_load(nvs) {
+            Ok(maintenance) => maintenance,
+            Err(e) => {
+                log::warn!(
+                    "Failed to load maintenance record: {:?}, creating a default",
+                    e
+                );
+                Self {
+                    nvs: nvs.clone(),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+
+    pub fn try_load(nvs: &Option<EspDefaultNvsPartition>) -> Result<Self, Error> {
+        let fs = KeyValueStore::new(nvs.clone())?;
+        let maintenance = FileType::Maintenance.load(&fs)?;
+
+        match maintenance {
+            File::Maintenance(mut maintenance) => {
+                maintenance.nvs = nvs.clone();
+                Ok(maintenance)
+            }
+            _ => Err(Error::NotFound("Maintenance".to_string())),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let mut fs = KeyValueStore::new(self.nvs.clone())?;
+        File::Maintenance(self.clone()).save(&mut fs)
+    }
+
+    // Before SNTP has set the clock the date is unknown, and a 1970 one would look long overdue
+    pub fn record_descale(&mut self) -> Result<(), Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.last_descale = if now >= CLOCK_SET_AFTER {
+            Some(now)
+        } else {
+            log::warn!("Clock isn't set, descale date not known");
+            None
+        };
+        self.save()
+    }
+
+    pub fn record_valve_actuations(&mut self, actuations: u64) -> Result<(), Error> {
+        self.valve_actuations = actuations;
+        self.save()
+    }
+}
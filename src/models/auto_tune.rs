@@ -1,7 +1,7 @@
 use crate::components::boiler::{Message as ElementMessage, Mode as ElementMode};
+use crate::sensors::reading::Sensor;
 use crate::types::{Temperature, Watts};
 use crate::{config::AutoTune as Config, models::boiler::BoilerModelParameters};
-use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 
 fn convert_to_dilated_time(duration: Duration) -> Duration {
@@ -140,14 +140,14 @@ pub struct HeuristicAutoTuner {
     state: HeuristicAutoTunerState,
     sample_time: Duration,
     // ambient_temperature: Option<Temperature>,
-    ambient_probe: Arc<RwLock<Temperature>>,
+    ambient_probe: Sensor<Temperature>,
     results: Option<BoilerModelParameters>,
     ambient_measurement: AmbientTest,
     current_power: Watts,
     element_power: ElementControlOption,
     modeled_temperature: Temperature,
     percentage_complete: f32,
-    temperature_probe: Arc<RwLock<Temperature>>,
+    temperature_probe: Sensor<Temperature>,
     pub boiler: Option<crate::components::boiler::Boiler>,
     config: Config,
 }
@@ -695,8 +695,8 @@ impl SteadyStateTest {
 impl HeuristicAutoTuner {
     pub fn new(
         sample_time: Duration,
-        temperature_probe: Arc<RwLock<Temperature>>,
-        ambient_probe: Arc<RwLock<Temperature>>,
+        temperature_probe: Sensor<Temperature>,
+        ambient_probe: Sensor<Temperature>,
         config: Config,
     ) -> Self {
         Self {
@@ -716,7 +716,7 @@ impl HeuristicAutoTuner {
     }
 
     fn get_probe(&self) -> Temperature {
-        *self.temperature_probe.read().unwrap()
+        self.temperature_probe.get()
     }
 
     pub fn get_model_boiler_temperature(&self) -> Temperature {
@@ -882,7 +882,7 @@ impl HeuristicAutoTuner {
         if let HeuristicAutoTunerState::MeasureHeatingUp(ref mut test) = self.state {
             match test.measure(current_temperature) {
                 HeatupTestState::Done(mut heatup_results) => {
-                    let ambient_temperature = *self.ambient_probe.read().unwrap();
+                    let ambient_temperature = self.ambient_probe.get();
                     let (estimated_temperature, _mpc) =
                         heatup_results.estimate_values_from_heatup(ambient_temperature)?;
                     let mut ambient_transfer_test = SteadyStateTest::new(
@@ -930,7 +930,7 @@ impl HeuristicAutoTuner {
 
                     log::info!("Estimating values from thermal transfer");
                     let results = test.estimate_values_from_thermal_transfer(
-                        *self.ambient_probe.read().unwrap(),
+                        self.ambient_probe.get(),
                         self.config.max_power,
                     )?;
 
@@ -1,7 +1,7 @@
 use crate::config::Boiler as Config;
+use crate::sensors::reading::Sensor;
 use crate::types::{Temperature, Watts};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 #[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
@@ -76,7 +76,7 @@ pub struct BoilerModel {
     // process variables
     pub probe_temperature: Temperature,
     boiler_temperature: Temperature,
-    ambient_probe: Arc<RwLock<Temperature>>,
+    ambient_probe: Sensor<Temperature>,
 
     power: Watts,
     smoothing_factor: f32,
@@ -84,11 +84,11 @@ pub struct BoilerModel {
 
 impl BoilerModel {
     pub fn new(
-        ambient_probe: Arc<RwLock<Temperature>>,
+        ambient_probe: Sensor<Temperature>,
         initial_temperature: Option<Temperature>,
         config: Config,
     ) -> Self {
-        let ambient_temperature = *ambient_probe.read().unwrap();
+        let ambient_temperature = ambient_probe.get();
         Self {
             max_power: config.power,
             parameters: config.mpc.parameters,
@@ -137,12 +137,39 @@ impl BoilerModel {
         self.power / self.max_power
     }
 
+    // How long heating flat out takes to bring the boiler to the setpoint, None if it never will
+    pub fn time_to_setpoint(
+        &self,
+        setpoint: Temperature,
+        ambient_temperature: Temperature,
+    ) -> Option<Duration> {
+        if self.boiler_temperature >= setpoint {
+            return Some(Duration::ZERO);
+        }
+
+        // With full power the boiler settles exponentially towards the temperature where the
+        // losses to ambient match the element
+        let settling_temperature =
+            ambient_temperature + self.max_power / self.parameters.ambient_transfer_coefficient;
+        if setpoint >= settling_temperature {
+            return None;
+        }
+        let time_constant =
+            self.parameters.thermal_mass / self.parameters.ambient_transfer_coefficient;
+        let seconds = time_constant
+            * ((settling_temperature - self.boiler_temperature)
+                / (settling_temperature - setpoint))
+                .ln();
+        // A zero transfer coefficient or a bad ambient reading leaves this NaN or negative
+        Duration::try_from_secs_f32(seconds).ok()
+    }
+
     pub fn update(&mut self, power: Watts, dt: Duration) -> (Temperature, Temperature) {
         let (delta_boiler_temperature, delta_probe_temperature) = self.parameters.system_model(
             power,
             self.boiler_temperature,
             self.probe_temperature,
-            *self.ambient_probe.read().unwrap(),
+            self.ambient_probe.get(),
             self.flow_rate_kg_per_sec,
             dt,
         );
@@ -1,2 +1,3 @@
 pub mod auto_tune;
 pub mod boiler;
+pub use rs_coffee_core::extraction;
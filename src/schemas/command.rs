@@ -0,0 +1,29 @@
+use crate::indicator::theme::Theme;
+use crate::types::{Bar, Temperature};
+use serde::{Deserialize, Serialize};
+
+// Everything that can be asked of the machine from REST, MQTT or the front panel
+// buttons, all of which are run by `System::execute`
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    PowerOn,
+    PowerOff,
+    SetTemperature(Temperature),
+    SetPressure(Bar),
+    Tare,
+    CycleDrink,
+    StartShot,
+    StopShot,
+    ToggleSteam,
+    SetTheme(Theme),
+    NextScreen,
+    PreviousScreen,
+    // Sent by the pump when it ends a shot itself, on time or on yield. Never accepted from
+    // outside, it would let a client end the shot behind the pump's back
+    #[serde(skip_deserializing)]
+    ShotFinished,
+    // Sent by the pump when it gives up on reaching a shot's yield
+    #[serde(skip_deserializing)]
+    ShotAbandoned,
+}
@@ -1,9 +1,7 @@
+pub mod command;
 pub mod drink;
-mod error;
 pub mod event;
-pub mod postinfusion;
-pub mod preinfusion;
-pub mod shot;
 pub mod status;
 
-pub use error::Error;
+#[cfg(feature = "sdcard")]
+pub use rs_coffee_core::schemas::formats;
@@ -1,3 +1,6 @@
+use crate::models::extraction::Metrics;
+use crate::sensors::ambient::Temperatures;
+use crate::sensors::reading::Reading;
 use crate::types::*;
 use serde::{Deserialize, Serialize};
 
@@ -7,14 +10,18 @@ pub struct Switches {
     pub water: bool,
     pub steam: bool,
 }
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+// Each sensor comes with how old its value is and how far it can be trusted
+#[derive(Serialize, Debug, Clone, Copy)]
 pub struct Device {
-    pub temperature: Temperature,
-    pub pressure: Bar,
-    pub weight: Grams,
-    pub ambient: Temperature,
+    pub temperature: Reading<Temperature>,
+    pub pressure: Reading<Bar>,
+    pub weight: Reading<Grams>,
+    pub ambient: Reading<Temperature>,
+    pub level: Reading<Millimeters>,
+    pub probes: Temperatures,
     pub power: Watts,
-    pub level: Millimeters,
+    pub volume: Milliliters,
+    pub fill: f32,
     pub switches: Switches,
 }
 
@@ -25,13 +32,16 @@ pub struct Operation {
     pub attributes: Option<serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone)]
 pub struct StatusReport {
     pub status: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
     pub device: Device,
     pub operation: Operation,
+    // Metrics from the most recent shot, for tracking consistency across a bag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shot: Option<Metrics>,
 }
 
 impl StatusReport {
@@ -1,4 +1,8 @@
-use crate::types::Millimeters;
+use crate::config::LevelSensor as Config;
+use crate::schemas::event::EventBuffer;
+use crate::sensors::health::Quality;
+use crate::sensors::reading::Sensor;
+use crate::types::{Milliliters, Millimeters};
 use esp_idf_hal::delay::NON_BLOCK;
 use esp_idf_hal::{
     gpio::{self, InputPin, OutputPin},
@@ -6,84 +10,296 @@ use esp_idf_hal::{
     prelude::*,
     uart::*,
 };
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
 use std::sync::{
     mpsc::{channel, Sender},
-    Arc, RwLock,
+    Arc, Mutex, RwLock,
 };
+use std::time::{Duration, Instant};
+
+// Datasheet measuring range
+const VALID_RANGE: RangeInclusive<Millimeters> = 30..=4500;
+
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MEDIAN_WINDOW: usize = 5;
+// Sloshing moves the surface a few mm either way, don't chase it
+const HYSTERESIS: Millimeters = 3;
+// Let the water settle before measuring what a draw used
+const SETTLE_TIME: Duration = Duration::from_secs(5);
+const LEAK_THRESHOLD: Milliliters = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Draw {
+    Shot,
+    HotWater,
+    Backflush,
+}
+
+impl std::fmt::Display for Draw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Draw::Shot => write!(f, "Shot"),
+            Draw::HotWater => write!(f, "Hot water"),
+            Draw::Backflush => write!(f, "Backflush"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Consumption {
+    pub shot: Milliliters,
+    pub hot_water: Milliliters,
+    pub backflush: Milliliters,
+    pub last: Option<(Draw, Milliliters)>,
+}
+
+impl Consumption {
+    fn record(&mut self, draw: Draw, used: Milliliters) {
+        match draw {
+            Draw::Shot => self.shot += used,
+            Draw::HotWater => self.hot_water += used,
+            Draw::Backflush => self.backflush += used,
+        }
+        self.last = Some((draw, used));
+    }
+}
 
 #[derive(Clone)]
 pub struct A02yyuw {
-    pub distance: Arc<RwLock<Millimeters>>,
+    pub consumption: Arc<RwLock<Consumption>>,
+    // Shared with the polling thread so a changed tank applies without a restart
+    pub config: Arc<RwLock<Config>>,
     mailbox: Sender<Message>,
 }
 
-#[allow(dead_code)]
 pub enum Message {
     DoRead,
+    StartDraw(Draw),
+    EndDraw,
 }
 
 impl A02yyuw {
     pub fn send_message(&self, message: Message) {
-        self.mailbox.send(message).unwrap();
+        // Nothing is listening when the board has no level sensor
+        let _ = self.mailbox.send(message);
+    }
+
+    pub fn absent(config: Config) -> Self {
+        let (mailbox, _) = channel();
+        Self {
+            consumption: Arc::new(RwLock::new(Consumption::default())),
+            config: Arc::new(RwLock::new(config)),
+            mailbox,
+        }
     }
     pub fn new<UART: Uart>(
         uart: impl Peripheral<P = UART> + 'static,
         rx: impl Peripheral<P = impl InputPin> + 'static,
         tx: impl Peripheral<P = impl OutputPin> + 'static,
+        distance: Sensor<Millimeters>,
+        events: Arc<Mutex<EventBuffer>>,
+        config: Config,
     ) -> Self {
         log::info!("Starting UART");
-        let config = config::Config::new().baudrate(Hertz(9600));
+        let uart_config = config::Config::new().baudrate(Hertz(9600));
         let uart = UartDriver::new(
             uart,
             tx,
             rx,
             Option::<gpio::Gpio0>::None,
             Option::<gpio::Gpio1>::None,
-            &config,
+            &uart_config,
         )
         .expect("Failed to initialize UART");
 
         let (tx, rx) = channel::<Message>();
-        let distance = Arc::new(RwLock::new(Millimeters::default()));
-        let distance_clone = distance.clone();
-        let polling_interval = std::time::Duration::from_secs(30);
+        let consumption = Arc::new(RwLock::new(Consumption::default()));
+        let config = Arc::new(RwLock::new(config));
+
+        let mut level = LevelInternal {
+            distance,
+            consumption: consumption.clone(),
+            events,
+            config: config.clone(),
+            filter: MedianFilter::default(),
+            draw: None,
+            settling: None,
+            idle_baseline: None,
+            leak_reported: false,
+        };
+
         log::info!("Starting A02YYUW thread");
         std::thread::spawn(move || loop {
-            // For now we really don't care why we returned, there's only one command
-            let _ = rx.recv_timeout(polling_interval);
-
-            let mut buffer1 = [0; 1];
-            let mut buffer2 = [0; 2];
-
-            log::info!("Reading buffer");
-            let start = std::time::Instant::now();
-            if loop {
-                if let Ok(1) = uart.read(&mut buffer1, NON_BLOCK) {
-                    if buffer1[0] != 0xFF {
-                        if let Ok(2) = uart.read(&mut buffer2, NON_BLOCK) {
-                            break true;
-                        }
-                    }
-                }
-                if start.elapsed() > std::time::Duration::from_secs(3) {
-                    log::warn!("Timeout reading buffer");
-                    *distance_clone.write().unwrap() = 0;
-                    break false;
-                }
-            } {
-                let expected = buffer1[0].wrapping_add(buffer2[0]).wrapping_add(0xFF);
-                if expected != buffer2[1] {
-                    log::warn!("Checksum mismatch: {:02X} != {:02X}", expected, buffer2[1]);
-                    continue;
-                }
-                *distance_clone.write().unwrap() =
-                    (buffer1[0] as Millimeters) << 8 | (buffer2[0] as Millimeters);
+            match rx.recv_timeout(level.poll_interval()) {
+                Ok(Message::StartDraw(draw)) => level.start_draw(draw),
+                Ok(Message::EndDraw) => level.end_draw(),
+                Ok(Message::DoRead) | Err(_) => {}
+            }
+
+            match read_distance(&uart) {
+                Ok(distance) => level.update(distance),
+                Err(quality) => level.distance.failure(quality),
             }
         });
 
         A02yyuw {
-            distance,
+            consumption,
+            config,
             mailbox: tx,
         }
     }
 }
+
+fn read_distance(uart: &UartDriver) -> Result<Millimeters, Quality> {
+    let mut buffer1 = [0; 1];
+    let mut buffer2 = [0; 2];
+
+    log::trace!("Reading buffer");
+    let start = Instant::now();
+    loop {
+        if let Ok(1) = uart.read(&mut buffer1, NON_BLOCK) {
+            if buffer1[0] != 0xFF {
+                if let Ok(2) = uart.read(&mut buffer2, NON_BLOCK) {
+                    break;
+                }
+            }
+        }
+        if start.elapsed() > Duration::from_secs(3) {
+            log::warn!("Timeout reading buffer");
+            return Err(Quality::NoResponse);
+        }
+    }
+
+    let expected = buffer1[0].wrapping_add(buffer2[0]).wrapping_add(0xFF);
+    if expected != buffer2[1] {
+        log::warn!("Checksum mismatch: {:02X} != {:02X}", expected, buffer2[1]);
+        return Err(Quality::ChecksumFailures);
+    }
+    let distance = (buffer1[0] as Millimeters) << 8 | (buffer2[0] as Millimeters);
+    if !VALID_RANGE.contains(&distance) {
+        log::warn!("Distance out of range: {}", distance);
+        return Err(Quality::OutOfRange);
+    }
+    Ok(distance)
+}
+
+#[derive(Default)]
+struct MedianFilter {
+    readings: VecDeque<Millimeters>,
+    published: Option<Millimeters>,
+}
+
+impl MedianFilter {
+    // Returns a new level once the median has moved further than the hysteresis band
+    fn update(&mut self, distance: Millimeters) -> Option<Millimeters> {
+        self.readings.push_back(distance);
+        while self.readings.len() > MEDIAN_WINDOW {
+            self.readings.pop_front();
+        }
+
+        let mut sorted: Vec<Millimeters> = self.readings.iter().copied().collect();
+        sorted.sort_unstable();
+        let median = sorted[sorted.len() / 2];
+
+        match self.published {
+            Some(published) if published.abs_diff(median) < HYSTERESIS => None,
+            _ => {
+                self.published = Some(median);
+                Some(median)
+            }
+        }
+    }
+}
+
+struct LevelInternal {
+    distance: Sensor<Millimeters>,
+    consumption: Arc<RwLock<Consumption>>,
+    events: Arc<Mutex<EventBuffer>>,
+    config: Arc<RwLock<Config>>,
+    filter: MedianFilter,
+    draw: Option<(Draw, Milliliters)>,
+    settling: Option<(Draw, Milliliters, Instant)>,
+    idle_baseline: Option<Milliliters>,
+    leak_reported: bool,
+}
+
+impl LevelInternal {
+    fn poll_interval(&self) -> Duration {
+        if self.draw.is_some() || self.settling.is_some() {
+            ACTIVE_POLL_INTERVAL
+        } else {
+            IDLE_POLL_INTERVAL
+        }
+    }
+
+    fn volume(&self) -> Milliliters {
+        self.config.read().unwrap().tank.volume(self.distance.get())
+    }
+
+    fn start_draw(&mut self, draw: Draw) {
+        if let Some((previous, start, _)) = self.settling.take() {
+            self.record(previous, start);
+        }
+        if let Some((previous, start)) = self.draw.take() {
+            self.record(previous, start);
+        }
+        self.idle_baseline = None;
+        self.draw = Some((draw, self.volume()));
+    }
+
+    fn end_draw(&mut self) {
+        if let Some((draw, start)) = self.draw.take() {
+            self.settling = Some((draw, start, Instant::now() + SETTLE_TIME));
+        }
+    }
+
+    fn record(&mut self, draw: Draw, start: Milliliters) {
+        let used = (start - self.volume()).max(0.0);
+        log::info!("{} used {:.0}mL", draw, used);
+        self.consumption.write().unwrap().record(draw, used);
+        self.events
+            .lock()
+            .unwrap()
+            .info(module_path!(), format!("{} used {:.0}mL", draw, used));
+    }
+
+    fn update(&mut self, distance: Millimeters) {
+        match self.filter.update(distance) {
+            Some(distance) => self.distance.update(distance),
+            // Within the hysteresis band, the published level is still current
+            None => self.distance.update(self.distance.get()),
+        }
+
+        if let Some((draw, start, settled_at)) = self.settling {
+            if Instant::now() >= settled_at {
+                self.settling = None;
+                self.record(draw, start);
+            }
+            return;
+        }
+        if self.draw.is_none() {
+            self.check_for_leak();
+        }
+    }
+
+    // Nothing should take water out of the tank while the machine is idle
+    fn check_for_leak(&mut self) {
+        let volume = self.volume();
+        let baseline = *self.idle_baseline.get_or_insert(volume);
+
+        if volume > baseline {
+            self.idle_baseline = Some(volume);
+            self.leak_reported = false;
+        } else if baseline - volume > LEAK_THRESHOLD && !self.leak_reported {
+            self.leak_reported = true;
+            log::warn!("Possible leak, lost {:.0}mL while idle", baseline - volume);
+            self.events.lock().unwrap().warn(
+                module_path!(),
+                format!("Possible leak, lost {:.0}mL while idle", baseline - volume),
+            );
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::sensors::health::Quality;
+use crate::sensors::reading::Sensor;
 use crate::types::Temperature;
 use ds18b20::{Ds18b20, Resolution};
 use esp_idf_hal::delay::Delay;
@@ -6,18 +8,105 @@ use esp_idf_hal::{
     gpio::{InputPin, OutputPin},
     peripheral::Peripheral,
 };
-use one_wire_bus::OneWire;
+use one_wire_bus::{Address, OneWire};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::RangeInclusive;
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
+// DS18B20 measuring range
+const VALID_RANGE: RangeInclusive<Temperature> = -55.0..=125.0;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+// Pick up probes plugged in after boot so they can be assigned
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(60);
+
+pub const MAX_PROBES: usize = 4;
+
+// Shown as hex in JSON so it matches what's printed on the probe, stored as a u64 in NVS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomAddress(pub u64);
+
+impl Serialize for RomAddress {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("{:016X}", self.0))
+        } else {
+            serializer.serialize_u64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RomAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            let address = String::deserialize(deserializer)?;
+            u64::from_str_radix(&address, 16)
+                .map(RomAddress)
+                .map_err(serde::de::Error::custom)
+        } else {
+            u64::deserialize(deserializer).map(RomAddress)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Ambient,
+    GroupHead,
+    SteamWand,
+    Enclosure,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Assignment {
+    pub address: RomAddress,
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Temperatures {
+    pub ambient: Option<Temperature>,
+    pub group_head: Option<Temperature>,
+    pub steam_wand: Option<Temperature>,
+    pub enclosure: Option<Temperature>,
+}
+
+impl Temperatures {
+    fn set(&mut self, role: Role, temperature: Option<Temperature>) {
+        match role {
+            Role::Ambient => self.ambient = temperature,
+            Role::GroupHead => self.group_head = temperature,
+            Role::SteamWand => self.steam_wand = temperature,
+            Role::Enclosure => self.enclosure = temperature,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct Discovered {
+    pub address: RomAddress,
+    pub role: Option<Role>,
+    pub temperature: Option<Temperature>,
+}
+
+// Reads every DS18B20 on the 1-Wire bus and publishes each under the role its
+// address is assigned to. With nothing assigned the first probe found is ambient.
 pub struct AmbientSensor {
-    pub temperature: Arc<RwLock<Temperature>>,
+    pub probes: Arc<RwLock<Temperatures>>,
+    pub discovered: Arc<RwLock<Vec<Discovered>>>,
 }
 
 impl AmbientSensor {
-    pub fn new(one_wire_pin: impl Peripheral<P = impl OutputPin + InputPin> + 'static) -> Self {
-        const GUESS_AT_AMBIENT_TEMP: Temperature = 25.0;
-        let temperature_probe = Arc::new(RwLock::new(GUESS_AT_AMBIENT_TEMP));
-        let temperature_probe_clone = temperature_probe.clone();
+    pub fn new(
+        one_wire_pin: impl Peripheral<P = impl OutputPin + InputPin> + 'static,
+        assignments: [Option<Assignment>; MAX_PROBES],
+        ambient: Sensor<Temperature>,
+    ) -> Self {
+        let probes = Arc::new(RwLock::new(Temperatures::default()));
+        let probes_clone = probes.clone();
+        let discovered = Arc::new(RwLock::new(Vec::new()));
+        let discovered_clone = discovered.clone();
 
         let mut delay = Delay::default();
         let one_wire_pin = PinDriver::input_output_od(one_wire_pin).unwrap();
@@ -26,101 +115,136 @@ impl AmbientSensor {
         std::thread::spawn(move || {
             #[cfg(feature = "simulate")]
             loop {
-                std::thread::sleep(std::time::Duration::from_secs(5));
-                *temperature_probe_clone.write().unwrap() = GUESS_AT_AMBIENT_TEMP;
+                std::thread::sleep(POLL_INTERVAL);
+                const GUESS_AT_AMBIENT_TEMP: Temperature = 25.0;
+                ambient.update(GUESS_AT_AMBIENT_TEMP);
+                probes_clone.write().unwrap().ambient = Some(GUESS_AT_AMBIENT_TEMP);
             }
-            let mut devices = 0;
-            while devices == 0 {
-                for device_address in one_wire_bus.devices(false, &mut delay) {
-                    match device_address {
-                        Ok(device_address) => {
-                            log::info!(
-                                "Found device at address {:?} with family code: {:#x?}",
-                                device_address,
-                                device_address.family_code()
-                            );
-                            devices += 1;
-                        }
-                        Err(e) => {
-                            log::error!("Error while searching for devices: {:?}", e);
-                            break;
-                        }
-                    }
+
+            let role_of = |address: &Address, first: bool| {
+                if assignments.iter().all(|a| a.is_none()) {
+                    return first.then_some(Role::Ambient);
                 }
-                std::thread::sleep(std::time::Duration::from_secs(2));
-            }
+                assignments
+                    .iter()
+                    .flatten()
+                    .find(|a| a.address.0 == address.0)
+                    .map(|a| a.role)
+            };
 
-            ds18b20::start_simultaneous_temp_measurement(&mut one_wire_bus, &mut delay).unwrap();
-            Resolution::Bits12.delay_for_measurement_time(&mut delay);
-
-            let mut search_state = None;
-            let sensor = loop {
-                match one_wire_bus.device_search(search_state.as_ref(), false, &mut delay) {
-                    Ok(Some((device_address, state))) => {
-                        search_state = Some(state);
-                        if device_address.family_code() != ds18b20::FAMILY_CODE {
-                            log::warn!("Device at {:?} has incorrect family code", device_address);
-                            continue;
-                        }
-                        let sensor: Ds18b20 = Ds18b20::new::<String>(device_address).unwrap();
-
-                        match sensor.read_data(&mut one_wire_bus, &mut delay) {
-                            Ok(sensor_data) => {
-                                *temperature_probe_clone.write().unwrap() = sensor_data.temperature;
-                                log::info!(
-                                    "Device at {:?} is {}°C",
-                                    device_address,
-                                    sensor_data.temperature
-                                );
+            // Unassigned probes are read too, so their temperature helps tell which is which
+            let mut sensors: Vec<(Ds18b20, Option<Role>)> = Vec::new();
+            let mut next_discovery = Instant::now();
+
+            loop {
+                if Instant::now() >= next_discovery {
+                    next_discovery = Instant::now() + DISCOVERY_INTERVAL;
+                    let mut addresses = Vec::new();
+                    for device_address in one_wire_bus.devices(false, &mut delay) {
+                        match device_address {
+                            Ok(address) if address.family_code() == ds18b20::FAMILY_CODE => {
+                                log::info!("Found DS18B20 at {:?}", address);
+                                addresses.push(address);
+                            }
+                            Ok(address) => {
+                                log::warn!("Device at {:?} has incorrect family code", address);
                             }
                             Err(e) => {
-                                log::warn!("Error reading data from device: {:?}", e);
+                                log::error!("Error while searching for devices: {:?}", e);
+                                break;
                             }
                         }
-                        /* Just grab the first one, there shouldn't be two */
-                        break sensor;
-                    }
-                    Ok(None) => {
-                        log::warn!("No more devices found");
-                        ds18b20::start_simultaneous_temp_measurement(&mut one_wire_bus, &mut delay)
-                            .unwrap();
-                        Resolution::Bits12.delay_for_measurement_time(&mut delay);
                     }
-                    Err(e) => {
-                        log::warn!("Error searching for devices: {:?}", e);
+
+                    sensors = addresses
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(i, address)| {
+                            Ds18b20::new::<String>(*address)
+                                .ok()
+                                .map(|sensor| (sensor, role_of(address, i == 0)))
+                        })
+                        .collect();
+                    *discovered_clone.write().unwrap() = addresses
+                        .iter()
+                        .enumerate()
+                        .map(|(i, address)| Discovered {
+                            address: RomAddress(address.0),
+                            role: role_of(address, i == 0),
+                            temperature: None,
+                        })
+                        .collect();
+
+                    if !sensors.iter().any(|(_, role)| *role == Some(Role::Ambient)) {
+                        log::warn!("No ambient probe found");
+                        ambient.failure(Quality::NoResponse);
                     }
                 }
-                std::thread::sleep(std::time::Duration::from_secs(5));
-            };
 
-            loop {
-                std::thread::sleep(std::time::Duration::from_secs(5));
-                match sensor.start_temp_measurement(&mut one_wire_bus, &mut delay) {
-                    Ok(_) => {
-                        Resolution::Bits12.delay_for_measurement_time(&mut delay);
-                        match sensor.read_data(&mut one_wire_bus, &mut delay) {
-                            Ok(sensor_data) => {
-                                *temperature_probe_clone.write().unwrap() = sensor_data.temperature;
-                                log::debug!(
-                                    "Device at {:?} is {}°C",
-                                    sensor.address(),
-                                    sensor_data.temperature
-                                );
+                if let Err(e) =
+                    ds18b20::start_simultaneous_temp_measurement(&mut one_wire_bus, &mut delay)
+                {
+                    log::warn!("Error starting temperature measurement: {:?}", e);
+                    ambient.failure(Quality::NoResponse);
+                    std::thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                Resolution::Bits12.delay_for_measurement_time(&mut delay);
+
+                let mut temperatures = Temperatures::default();
+                for (sensor, role) in sensors.iter() {
+                    let is_ambient = *role == Some(Role::Ambient);
+                    let temperature = match sensor.read_data(&mut one_wire_bus, &mut delay) {
+                        Ok(data) if VALID_RANGE.contains(&data.temperature) => {
+                            log::debug!(
+                                "Probe at {:?} ({:?}) is {}°C",
+                                sensor.address(),
+                                role,
+                                data.temperature
+                            );
+                            if is_ambient {
+                                ambient.update(data.temperature);
                             }
-                            Err(e) => {
-                                log::warn!("Error reading data from device: {:?}", e);
+                            Some(data.temperature)
+                        }
+                        Ok(data) => {
+                            log::warn!(
+                                "Probe at {:?} temperature out of range: {}",
+                                sensor.address(),
+                                data.temperature
+                            );
+                            if is_ambient {
+                                ambient.fault(Quality::OutOfRange);
                             }
+                            None
                         }
+                        Err(e) => {
+                            log::warn!("Error reading probe at {:?}: {:?}", sensor.address(), e);
+                            if is_ambient {
+                                ambient.failure(Quality::NoResponse);
+                            }
+                            None
+                        }
+                    };
+                    if let Some(role) = role {
+                        temperatures.set(*role, temperature);
                     }
-                    Err(e) => {
-                        log::warn!("Error starting temperature measurement: {:?}", e);
+
+                    if let Some(entry) = discovered_clone
+                        .write()
+                        .unwrap()
+                        .iter_mut()
+                        .find(|d| d.address.0 == sensor.address().0)
+                    {
+                        entry.temperature = temperature;
                     }
                 }
+                *probes_clone.write().unwrap() = temperatures;
+
+                std::thread::sleep(POLL_INTERVAL);
             }
         });
 
-        Self {
-            temperature: temperature_probe,
-        }
+        Self { probes, discovered }
     }
 }
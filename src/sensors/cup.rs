@@ -0,0 +1,102 @@
+use crate::types::Grams;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const SETTLE_WINDOW: Duration = Duration::from_millis(750);
+const SETTLE_MIN_SAMPLES: usize = 3;
+const SETTLE_TOLERANCE: Grams = 1.0;
+// Smallest change worth reporting, anything below is drift
+const MIN_STEP: Grams = 1.0;
+const CUP_MIN_WEIGHT: Grams = 50.0;
+// A cup lands or lifts in one movement, liquid arrives gradually
+const SUDDEN_STEP: Duration = Duration::from_millis(1500);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CupEvent {
+    Placed(Grams),
+    Removed(Grams),
+    LiquidAdded(Grams),
+}
+
+impl std::fmt::Display for CupEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CupEvent::Placed(grams) => write!(f, "Cup placed ({:.1}g)", grams),
+            CupEvent::Removed(grams) => write!(f, "Cup removed ({:.1}g)", grams),
+            CupEvent::LiquidAdded(grams) => write!(f, "Liquid added ({:.1}g)", grams),
+        }
+    }
+}
+
+// Classifies the change between two settled weights by its size and how long the
+// weight took to settle again
+#[derive(Default)]
+pub struct CupDetector {
+    recent: VecDeque<(Instant, Grams)>,
+    settled: Option<Grams>,
+    moving_since: Option<Instant>,
+    cup_present: bool,
+}
+
+impl CupDetector {
+    // Forget the settled weight after a tare, the cup is still where it was
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.settled = None;
+        self.moving_since = None;
+    }
+
+    pub fn update(&mut self, now: Instant, weight: Grams) -> Option<CupEvent> {
+        self.recent.push_back((now, weight));
+        while self
+            .recent
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > SETTLE_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+
+        let (min, max) = self
+            .recent
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), (_, w)| {
+                (min.min(*w), max.max(*w))
+            });
+        if max - min > SETTLE_TOLERANCE {
+            self.moving_since.get_or_insert(now);
+            return None;
+        }
+        if self.recent.len() < SETTLE_MIN_SAMPLES {
+            return None;
+        }
+
+        let mean = self.recent.iter().map(|(_, w)| w).sum::<f32>() / self.recent.len() as f32;
+        let Some(settled) = self.settled else {
+            self.settled = Some(mean);
+            return None;
+        };
+
+        let delta = mean - settled;
+        let sudden = self
+            .moving_since
+            .take()
+            .map(|since| now.duration_since(since) <= SUDDEN_STEP + SETTLE_WINDOW)
+            .unwrap_or(false);
+        if delta.abs() < MIN_STEP {
+            return None;
+        }
+        self.settled = Some(mean);
+
+        if sudden && delta <= -CUP_MIN_WEIGHT {
+            self.cup_present = false;
+            Some(CupEvent::Removed(-delta))
+        } else if sudden && delta >= CUP_MIN_WEIGHT && !self.cup_present {
+            self.cup_present = true;
+            Some(CupEvent::Placed(delta))
+        } else if delta > 0.0 {
+            Some(CupEvent::LiquidAdded(delta))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::types::Grams;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(2);
+const MIN_POINTS: usize = 4;
+// Residual from the fitted line past which a sample is treated as a bump on the cup
+const OUTLIER_THRESHOLD: Grams = 2.0;
+
+const DRIP_WEIGHT: Grams = 0.5;
+const DRIP_FLOW: f32 = 0.2;
+const DRIP_CONFIRMATIONS: usize = 3;
+
+// Least-squares slope of weight against time over a sliding window, refitted once
+// without the samples that sit far from the first fit
+#[derive(Default)]
+pub struct FlowEstimator {
+    history: VecDeque<(Instant, Grams)>,
+}
+
+impl FlowEstimator {
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn update(&mut self, now: Instant, weight: Grams) -> f32 {
+        self.history.push_back((now, weight));
+        while self
+            .history
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > WINDOW)
+        {
+            self.history.pop_front();
+        }
+
+        let Some((start, _)) = self.history.front().copied() else {
+            return 0.0;
+        };
+        let points: Vec<(f32, f32)> = self
+            .history
+            .iter()
+            .map(|(t, w)| (t.duration_since(start).as_secs_f32(), *w))
+            .collect();
+
+        let Some((slope, intercept)) = fit(&points) else {
+            return 0.0;
+        };
+
+        let inliers: Vec<(f32, f32)> = points
+            .iter()
+            .copied()
+            .filter(|(t, w)| (w - (slope * t + intercept)).abs() <= OUTLIER_THRESHOLD)
+            .collect();
+
+        if inliers.len() == points.len() {
+            slope
+        } else {
+            fit(&inliers).map(|(slope, _)| slope).unwrap_or(0.0)
+        }
+    }
+}
+
+fn fit(points: &[(f32, f32)]) -> Option<(f32, f32)> {
+    if points.len() < MIN_POINTS {
+        return None;
+    }
+    let n = points.len() as f32;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f32>() / n;
+    let mean_w = points.iter().map(|(_, w)| w).sum::<f32>() / n;
+    let (covariance, variance) = points.iter().fold((0.0, 0.0), |(c, v), (t, w)| {
+        (c + (t - mean_t) * (w - mean_w), v + (t - mean_t).powi(2))
+    });
+    if variance <= f32::EPSILON {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((slope, mean_w - slope * mean_t))
+}
+
+// Watches for the first liquid reaching the cup once a shot has been started
+#[derive(Default)]
+pub struct DripDetector {
+    armed: bool,
+    confirmations: usize,
+    first_seen: Option<Instant>,
+}
+
+impl DripDetector {
+    pub fn arm(&mut self) {
+        *self = Self {
+            armed: true,
+            ..Default::default()
+        };
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = false;
+    }
+
+    // Returns when the first drip landed, once it has been seen for long enough
+    pub fn update(&mut self, now: Instant, weight: Grams, flow: f32) -> Option<Instant> {
+        if !self.armed {
+            return None;
+        }
+        if weight < DRIP_WEIGHT || flow < DRIP_FLOW {
+            self.confirmations = 0;
+            self.first_seen = None;
+            return None;
+        }
+
+        let first_seen = *self.first_seen.get_or_insert(now);
+        self.confirmations += 1;
+        if self.confirmations < DRIP_CONFIRMATIONS {
+            return None;
+        }
+
+        self.armed = false;
+        Some(first_seen)
+    }
+}
@@ -0,0 +1,235 @@
+use crate::state_machines::system_fsm::{SystemState, Transition};
+use crate::state_machines::FsmError;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Quality {
+    #[default]
+    Unknown,
+    Good,
+    OpenCircuit,
+    ShortCircuit,
+    OutOfRange,
+    Stale,
+    NoResponse,
+    ChecksumFailures,
+}
+
+impl std::fmt::Display for Quality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Quality::Unknown => write!(f, "Unknown"),
+            Quality::Good => write!(f, "Good"),
+            Quality::OpenCircuit => write!(f, "Open circuit"),
+            Quality::ShortCircuit => write!(f, "Short circuit"),
+            Quality::OutOfRange => write!(f, "Out of range"),
+            Quality::Stale => write!(f, "Stale"),
+            Quality::NoResponse => write!(f, "No response"),
+            Quality::ChecksumFailures => write!(f, "Checksum failures"),
+        }
+    }
+}
+
+impl Quality {
+    pub fn is_fault(&self) -> bool {
+        !matches!(self, Quality::Good | Quality::Unknown)
+    }
+}
+
+struct HealthInternal {
+    quality: Quality,
+    last_good: Option<Instant>,
+    failure_streak: usize,
+}
+
+#[derive(Clone)]
+pub struct Health {
+    inner: Arc<RwLock<HealthInternal>>,
+    stale_after: Duration,
+    max_failure_streak: usize,
+}
+
+impl Health {
+    pub fn new(stale_after: Duration, max_failure_streak: usize) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(HealthInternal {
+                quality: Quality::Unknown,
+                last_good: None,
+                failure_streak: 0,
+            })),
+            stale_after,
+            max_failure_streak,
+        }
+    }
+
+    pub fn good(&self) {
+        let mut inner = self.inner.write().unwrap();
+        inner.quality = Quality::Good;
+        inner.last_good = Some(Instant::now());
+        inner.failure_streak = 0;
+    }
+
+    // A fault that is unambiguous from a single reading, e.g. an open circuit
+    pub fn fault(&self, quality: Quality) {
+        let mut inner = self.inner.write().unwrap();
+        if inner.quality != quality {
+            log::warn!("Sensor fault: {}", quality);
+        }
+        inner.quality = quality;
+    }
+
+    // A fault that is only reported once it has happened several times in a row
+    pub fn failure(&self, quality: Quality) {
+        let mut inner = self.inner.write().unwrap();
+        inner.failure_streak += 1;
+        if inner.failure_streak >= self.max_failure_streak {
+            if inner.quality != quality {
+                log::warn!(
+                    "Sensor fault: {} after {} failures",
+                    quality,
+                    inner.failure_streak
+                );
+            }
+            inner.quality = quality;
+        }
+    }
+
+    pub fn quality(&self) -> Quality {
+        let inner = self.inner.read().unwrap();
+        match inner.last_good {
+            Some(last_good) if inner.quality == Quality::Good => {
+                if last_good.elapsed() > self.stale_after {
+                    Quality::Stale
+                } else {
+                    Quality::Good
+                }
+            }
+            None if inner.quality == Quality::Unknown => Quality::Unknown,
+            _ => inner.quality,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.quality() == Quality::Good
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Report {
+    pub temperature: Quality,
+    pub pressure: Quality,
+    pub weight: Quality,
+    pub ambient: Quality,
+    pub level: Quality,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone)]
+pub struct SensorHealth {
+    pub temperature: Health,
+    pub pressure: Health,
+    pub weight: Health,
+    pub ambient: Health,
+    pub level: Health,
+    raised: Arc<Mutex<Option<Severity>>>,
+}
+
+impl SensorHealth {
+    pub fn new(sensors: &crate::sensors::registry::Registry) -> Self {
+        Self {
+            temperature: sensors.temperature.health().clone(),
+            pressure: sensors.pressure.health().clone(),
+            weight: sensors.weight.health().clone(),
+            ambient: sensors.ambient.health().clone(),
+            level: sensors.level.health().clone(),
+            raised: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn report(&self) -> Report {
+        Report {
+            temperature: self.temperature.quality(),
+            pressure: self.pressure.quality(),
+            weight: self.weight.quality(),
+            ambient: self.ambient.quality(),
+            level: self.level.quality(),
+        }
+    }
+
+    // Without a valid boiler probe we can't heat safely, every other sensor only
+    // degrades what we can do so they raise a warning
+    fn fault(&self) -> Option<(Severity, String)> {
+        let report = self.report();
+        if report.temperature.is_fault() {
+            return Some((
+                Severity::Error,
+                format!("Boiler probe: {}", report.temperature),
+            ));
+        }
+
+        let warnings: Vec<String> = [
+            ("Pressure", report.pressure),
+            ("Scale", report.weight),
+            ("Ambient probe", report.ambient),
+            ("Level sensor", report.level),
+        ]
+        .iter()
+        .filter(|(_, quality)| quality.is_fault())
+        .map(|(sensor, quality)| format!("{}: {}", sensor, quality))
+        .collect();
+
+        if warnings.is_empty() {
+            None
+        } else {
+            Some((Severity::Warning, warnings.join(", ")))
+        }
+    }
+
+    pub fn update_system_state(&self, state: &mut SystemState) -> Result<Option<String>, FsmError> {
+        let mut raised = self.raised.lock().unwrap();
+        let fault = self.fault();
+
+        let transition = match (&*state, &fault, *raised) {
+            (
+                SystemState::Healthy | SystemState::Warning(_),
+                Some((Severity::Error, message)),
+                _,
+            ) => Some(Transition::Error(message.clone())),
+            (SystemState::Healthy, Some((Severity::Warning, message)), _) => {
+                Some(Transition::Warning(message.clone()))
+            }
+            (SystemState::Warning(current), Some((Severity::Warning, message)), _)
+                if current != message =>
+            {
+                Some(Transition::Warning(message.clone()))
+            }
+            (SystemState::Warning(_), None, Some(Severity::Warning)) => {
+                Some(Transition::ClearWarnings)
+            }
+            (SystemState::Error(_), None | Some((Severity::Warning, _)), Some(Severity::Error)) => {
+                Some(Transition::ClearErrros)
+            }
+            _ => None,
+        };
+
+        let Some(transition) = transition else {
+            return Ok(None);
+        };
+
+        let message = transition.to_string();
+        state.transition(transition)?;
+        *raised = match *state {
+            SystemState::Error(_) => Some(Severity::Error),
+            SystemState::Warning(_) => Some(Severity::Warning),
+            _ => None,
+        };
+        Ok(Some(message))
+    }
+}
@@ -0,0 +1,262 @@
+use crate::sensors::health::Quality;
+use crate::sensors::pt100::{
+    diagnose_resistance, resistance_to_degrees, Calibration, OPEN_CIRCUIT_RESISTANCE,
+    SHORT_CIRCUIT_RESISTANCE,
+};
+use crate::sensors::traits::TemperatureProbe;
+use embedded_hal::spi::{Operation, SpiDevice};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+const REGISTER_CONFIG: u8 = 0x00;
+const REGISTER_RTD: u8 = 0x01;
+// High threshold MSB/LSB followed by low threshold MSB/LSB, written in one go
+const REGISTER_FAULT_THRESHOLDS: u8 = 0x03;
+const REGISTER_FAULT_STATUS: u8 = 0x07;
+const WRITE: u8 = 0x80;
+
+const CONFIG_VBIAS: u8 = 1 << 7;
+const CONFIG_AUTO_CONVERSION: u8 = 1 << 6;
+const CONFIG_THREE_WIRE: u8 = 1 << 4;
+const CONFIG_FAULT_DETECTION: u8 = 0b11 << 2;
+const CONFIG_FAULT_DETECTION_AUTO: u8 = 0b01 << 2;
+const CONFIG_FAULT_CLEAR: u8 = 1 << 1;
+const CONFIG_FILTER_50HZ: u8 = 1 << 0;
+
+const FAULT_RTD_HIGH_THRESHOLD: u8 = 1 << 7;
+const FAULT_RTD_LOW_THRESHOLD: u8 = 1 << 6;
+const FAULT_REFIN_HIGH: u8 = 1 << 5;
+const FAULT_REFIN_LOW: u8 = 1 << 4;
+const FAULT_RTDIN_LOW: u8 = 1 << 3;
+
+const RTD_FULL_SCALE: f64 = 32768.0;
+
+// The same pace as the averaged ADC readings the boiler loop was tuned on
+const READ_INTERVAL: Duration = Duration::from_secs(1);
+// Detection stops conversions for a moment, so only check the wiring now and then
+const FAULT_DETECTION_INTERVAL: Duration = Duration::from_secs(60);
+// The automatic cycle takes about 550 us, the first conversion after it about 65 ms
+const FAULT_DETECTION_TIMEOUT: Duration = Duration::from_millis(10);
+const FIRST_CONVERSION: Duration = Duration::from_millis(70);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Wires {
+    Two,
+    Three,
+    Four,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Config {
+    pub wires: Wires,
+    pub reference_resistor: f32,
+    pub filter_50hz: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // The breakout boards ship with a 430R reference for Pt100
+        const REFERENCE_RESISTOR: f32 = 430.0;
+        Config {
+            wires: Wires::Two,
+            reference_resistor: REFERENCE_RESISTOR,
+            filter_50hz: true,
+        }
+    }
+}
+
+impl Config {
+    fn bits(&self) -> u8 {
+        let mut bits = CONFIG_VBIAS | CONFIG_AUTO_CONVERSION;
+        if self.wires == Wires::Three {
+            bits |= CONFIG_THREE_WIRE;
+        }
+        if self.filter_50hz {
+            bits |= CONFIG_FILTER_50HZ;
+        }
+        bits
+    }
+
+    // Fault threshold register value for a resistance, the ratio sits in the top 15 bits
+    fn threshold(&self, resistance: f64) -> [u8; 2] {
+        let code = (resistance / self.reference_resistor as f64 * RTD_FULL_SCALE)
+            .round()
+            .clamp(0.0, RTD_FULL_SCALE - 1.0) as u16;
+        (code << 1).to_be_bytes()
+    }
+}
+
+// Converts the MAX31865 ratio register (RTD / reference resistor) to degrees
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+pub struct Max31865 {
+    pub config: Config,
+    pub calibration: Calibration,
+}
+
+impl Max31865 {
+    pub fn new(config: Config, calibration: Calibration) -> Self {
+        Self {
+            config,
+            calibration,
+        }
+    }
+
+    fn convert_ratio_to_resistance(&self, ratio: f64) -> f64 {
+        ratio * self.config.reference_resistor as f64
+    }
+}
+
+impl TemperatureProbe for Max31865 {
+    fn convert_voltage_to_degrees(&self, ratio: f64) -> Result<f32, String> {
+        let resistance = self.convert_ratio_to_resistance(ratio);
+        let resistance = self
+            .calibration
+            .correct(resistance, |r| self.convert_ratio_to_resistance(r));
+        resistance_to_degrees(resistance)
+    }
+
+    fn resistance(&self, ratio: f64) -> f64 {
+        self.convert_ratio_to_resistance(ratio)
+    }
+
+    fn diagnose(&self, ratio: f64) -> Quality {
+        diagnose_resistance(self.convert_ratio_to_resistance(ratio))
+    }
+}
+
+pub struct Max31865Driver<SPI: SpiDevice> {
+    spi: SPI,
+    config: Config,
+    next_read: Instant,
+    next_fault_detection: Instant,
+}
+
+impl<SPI: SpiDevice> Max31865Driver<SPI> {
+    pub fn new(spi: SPI, config: Config) -> Result<Self, String> {
+        let now = Instant::now();
+        let mut driver = Self {
+            spi,
+            config,
+            next_read: now,
+            // The first read runs a detection cycle so wiring faults show up at start-up
+            next_fault_detection: now,
+        };
+        let [high_msb, high_lsb] = config.threshold(OPEN_CIRCUIT_RESISTANCE);
+        let [low_msb, low_lsb] = config.threshold(SHORT_CIRCUIT_RESISTANCE);
+        driver
+            .spi
+            .write(&[
+                WRITE | REGISTER_FAULT_THRESHOLDS,
+                high_msb,
+                high_lsb,
+                low_msb,
+                low_lsb,
+            ])
+            .map_err(|e| format!("Failed to set MAX31865 fault thresholds: {:?}", e))?;
+        driver
+            .write_register(REGISTER_CONFIG, config.bits() | CONFIG_FAULT_CLEAR)
+            .map_err(|e| format!("Failed to configure MAX31865: {:?}", e))?;
+        Ok(driver)
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<(), SPI::Error> {
+        self.spi.write(&[WRITE | register, value])
+    }
+
+    fn read_registers(&mut self, register: u8, buffer: &mut [u8]) -> Result<(), SPI::Error> {
+        self.spi
+            .transaction(&mut [Operation::Write(&[register]), Operation::Read(buffer)])
+    }
+
+    // Call as often as you like, returns a ratio or fault when one is due
+    pub fn read(&mut self) -> Option<Result<f64, Quality>> {
+        let now = Instant::now();
+        if now >= self.next_fault_detection {
+            self.next_fault_detection = now + FAULT_DETECTION_INTERVAL;
+            self.next_read = now + FIRST_CONVERSION;
+            return self.detect_faults().err().map(Err);
+        }
+        if now < self.next_read {
+            return None;
+        }
+        self.next_read = now + READ_INTERVAL;
+        Some(self.read_ratio())
+    }
+
+    // Returns RTD / reference, or the fault the converter flagged alongside the reading
+    pub fn read_ratio(&mut self) -> Result<f64, Quality> {
+        let mut buffer = [0; 2];
+        self.read_registers(REGISTER_RTD, &mut buffer)
+            .map_err(|e| {
+                log::error!("Failed to read MAX31865 RTD register: {:?}", e);
+                Quality::NoResponse
+            })?;
+
+        let raw = u16::from_be_bytes(buffer);
+        if raw & 0x01 != 0 {
+            return Err(self.read_fault().unwrap_or(Quality::OutOfRange));
+        }
+
+        Ok((raw >> 1) as f64 / RTD_FULL_SCALE)
+    }
+
+    // Runs the automatic fault detection cycle, which catches an open or shorted RTD and
+    // reference even while the thresholds aren't tripped
+    pub fn detect_faults(&mut self) -> Result<(), Quality> {
+        let bits = (self.config.bits() & !CONFIG_AUTO_CONVERSION) | CONFIG_FAULT_DETECTION_AUTO;
+        self.write_register(REGISTER_CONFIG, bits).map_err(|e| {
+            log::error!("Failed to start MAX31865 fault detection: {:?}", e);
+            Quality::NoResponse
+        })?;
+
+        let started = Instant::now();
+        loop {
+            let mut config = [0; 1];
+            if let Err(e) = self.read_registers(REGISTER_CONFIG, &mut config) {
+                log::error!("Failed to read MAX31865 config: {:?}", e);
+                return Err(Quality::NoResponse);
+            }
+            if config[0] & CONFIG_FAULT_DETECTION == 0 {
+                break;
+            }
+            if started.elapsed() > FAULT_DETECTION_TIMEOUT {
+                log::error!("MAX31865 fault detection didn't finish");
+                self.read_fault();
+                return Err(Quality::NoResponse);
+            }
+        }
+
+        self.read_fault().map_or(Ok(()), Err)
+    }
+
+    // Reads and clears the fault status, which also restarts automatic conversions
+    fn read_fault(&mut self) -> Option<Quality> {
+        let mut status = [0; 1];
+        if let Err(e) = self.read_registers(REGISTER_FAULT_STATUS, &mut status) {
+            log::error!("Failed to read MAX31865 fault status: {:?}", e);
+            return Some(Quality::NoResponse);
+        }
+        if let Err(e) =
+            self.write_register(REGISTER_CONFIG, self.config.bits() | CONFIG_FAULT_CLEAR)
+        {
+            log::error!("Failed to clear MAX31865 fault: {:?}", e);
+        }
+
+        let status = status[0];
+        if status == 0 {
+            return None;
+        }
+        log::warn!("MAX31865 fault status: {:#04x}", status);
+        Some(if status & FAULT_RTD_LOW_THRESHOLD != 0 {
+            Quality::ShortCircuit
+        } else if status
+            & (FAULT_RTD_HIGH_THRESHOLD | FAULT_REFIN_HIGH | FAULT_REFIN_LOW | FAULT_RTDIN_LOW)
+            != 0
+        {
+            Quality::OpenCircuit
+        } else {
+            // FAULT_OVER_UNDER_VOLTAGE
+            Quality::OutOfRange
+        })
+    }
+}
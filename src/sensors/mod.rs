@@ -1,6 +1,13 @@
 pub mod a02yyuw;
 pub mod ambient;
+pub mod cup;
+pub mod flow;
+pub mod health;
+pub mod max31865;
 pub mod pressure;
 pub mod pt100;
+pub mod reading;
+pub mod registry;
 pub mod scale;
+pub mod tank;
 pub mod traits;
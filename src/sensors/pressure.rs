@@ -1,19 +1,111 @@
+use crate::sensors::health::Quality;
 use crate::sensors::traits::PressureProbe;
+use crate::types::Bar;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
-pub struct SeeedWaterPressureSensor {
-    vcc: f32,
+const BAR_PER_PSI: f32 = 0.0689476;
+const BAR_PER_MPA: f32 = 10.0;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Unit {
+    Bar,
+    Psi,
+    Mpa,
 }
 
-impl Default for SeeedWaterPressureSensor {
+impl Unit {
+    fn to_bar(self, value: f32) -> Bar {
+        match self {
+            Unit::Bar => value,
+            Unit::Psi => value * BAR_PER_PSI,
+            Unit::Mpa => value * BAR_PER_MPA,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Preset {
+    // 0-1.2 MPa, 0.5-4.5 V on a 5 V supply (Seeed G1/4 and the common clones)
+    Mpa1_2,
+    // 0-200 psi, 0.5-4.5 V on a 5 V supply
+    Psi200,
+    // Seeed G1/4 powered from 3.3 V with no divider, its output is 0.1-1.0 of the supply
+    // across 0-1.2 MPa. This is how the original board wires it.
+    Seeed3v3,
+}
+
+// A linear transducer whose output sits between `output_min` and `output_max` volts
+// across its range, seen by the ADC through a resistor divider of `divider`
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Transducer {
+    pub range: f32,
+    pub unit: Unit,
+    pub output_min: f32,
+    pub output_max: f32,
+    pub divider: f32,
+    pub zero_offset: Bar,
+}
+
+// Matches the wiring of machines set up before the transducer was configurable
+impl Default for Transducer {
     fn default() -> Self {
-        SeeedWaterPressureSensor { vcc: 3.3 }
+        Transducer::from(Preset::Seeed3v3)
+    }
+}
+
+impl From<Preset> for Transducer {
+    fn from(preset: Preset) -> Self {
+        // 10k over 20k brings 4.5 V down to 3 V for the ESP32
+        const DIVIDER: f32 = 2.0 / 3.0;
+        const SUPPLY_3V3: f32 = 3.3;
+        let (range, unit, output_min, output_max, divider) = match preset {
+            Preset::Mpa1_2 => (1.2, Unit::Mpa, 0.5, 4.5, DIVIDER),
+            Preset::Psi200 => (200.0, Unit::Psi, 0.5, 4.5, DIVIDER),
+            Preset::Seeed3v3 => (1.2, Unit::Mpa, 0.1 * SUPPLY_3V3, SUPPLY_3V3, 1.0),
+        };
+        Transducer {
+            range,
+            unit,
+            output_min,
+            output_max,
+            divider,
+            zero_offset: 0.0,
+        }
+    }
+}
+
+impl Transducer {
+    fn sensor_voltage(&self, voltage: f64) -> f32 {
+        voltage as f32 / self.divider
+    }
+
+    // Pressure before the zero offset is taken off, used to capture the offset
+    pub fn convert_uncorrected(&self, voltage: f64) -> Bar {
+        let span = self.output_max - self.output_min;
+        let fraction = (self.sensor_voltage(voltage) - self.output_min) / span;
+        self.unit.to_bar(fraction * self.range)
+    }
+
+    // These transducers never drive their output to either rail, so a reading well
+    // outside the output span means a broken wire or a shorted signal line
+    pub fn diagnose(&self, voltage: f64) -> Quality {
+        let voltage = self.sensor_voltage(voltage);
+        let margin = self.output_min / 2.0;
+        if voltage < self.output_min - margin {
+            Quality::OpenCircuit
+        } else if voltage > self.output_max + margin {
+            Quality::ShortCircuit
+        } else {
+            Quality::Good
+        }
     }
 }
 
-impl PressureProbe for SeeedWaterPressureSensor {
+impl PressureProbe for Transducer {
     fn convert_voltage_to_pressure(&self, voltage: f64) -> Result<f32, String> {
-        Ok(((voltage / self.vcc as f64 - 0.1) / 0.75) as f32)
+        if self.output_max <= self.output_min || self.divider <= 0.0 {
+            return Err("Invalid pressure transducer configuration".to_string());
+        }
+        Ok(self.convert_uncorrected(voltage) - self.zero_offset)
     }
 }
@@ -0,0 +1,73 @@
+use crate::sensors::health::{Health, Quality};
+use serde::{Serialize, Serializer};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Reading<T> {
+    pub value: T,
+    #[serde(rename = "age_ms", serialize_with = "serialize_age")]
+    pub timestamp: Option<Instant>,
+    pub quality: Quality,
+}
+
+// Instants only mean something on this device, so report how old the value is instead
+fn serialize_age<S: Serializer>(
+    timestamp: &Option<Instant>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    timestamp
+        .map(|timestamp| timestamp.elapsed().as_millis() as u64)
+        .serialize(serializer)
+}
+
+// The latest value from a sensor along with when it was taken. Quality and
+// staleness come from the sensor's `Health`.
+#[derive(Clone)]
+pub struct Sensor<T> {
+    value: Arc<RwLock<(T, Option<Instant>)>>,
+    health: Health,
+}
+
+impl<T: Copy> Sensor<T> {
+    pub fn new(initial: T, health: Health) -> Self {
+        Self {
+            value: Arc::new(RwLock::new((initial, None))),
+            health,
+        }
+    }
+
+    pub fn get(&self) -> T {
+        self.value.read().unwrap().0
+    }
+
+    pub fn reading(&self) -> Reading<T> {
+        let (value, timestamp) = *self.value.read().unwrap();
+        Reading {
+            value,
+            timestamp,
+            quality: self.health.quality(),
+        }
+    }
+
+    pub fn update(&self, value: T) {
+        *self.value.write().unwrap() = (value, Some(Instant::now()));
+        self.health.good();
+    }
+
+    pub fn fault(&self, quality: Quality) {
+        self.health.fault(quality);
+    }
+
+    pub fn failure(&self, quality: Quality) {
+        self.health.failure(quality);
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.health.is_valid()
+    }
+
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+}
@@ -0,0 +1,59 @@
+use crate::sensors::health::Health;
+use crate::sensors::reading::{Reading, Sensor};
+use crate::types::{Bar, Grams, Millimeters, Temperature};
+use serde::Serialize;
+use std::time::Duration;
+
+const GUESS_AT_AMBIENT_TEMP: Temperature = 25.0;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Readings {
+    pub temperature: Reading<Temperature>,
+    pub pressure: Reading<Bar>,
+    pub weight: Reading<Grams>,
+    pub ambient: Reading<Temperature>,
+    pub level: Reading<Millimeters>,
+}
+
+// Every sensor the rest of the firmware reads from, along with how often each is
+// expected to update and how many bad reads it takes to call it faulty
+#[derive(Clone)]
+pub struct Registry {
+    pub temperature: Sensor<Temperature>,
+    pub pressure: Sensor<Bar>,
+    pub weight: Sensor<Grams>,
+    pub ambient: Sensor<Temperature>,
+    pub level: Sensor<Millimeters>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self {
+            temperature: Sensor::new(0.0, Health::new(Duration::from_secs(5), 5)),
+            pressure: Sensor::new(0.0, Health::new(Duration::from_secs(5), 5)),
+            weight: Sensor::new(0.0, Health::new(Duration::from_secs(5), 10)),
+            ambient: Sensor::new(
+                GUESS_AT_AMBIENT_TEMP,
+                Health::new(Duration::from_secs(30), 3),
+            ),
+            // Polled every 30 s while idle
+            level: Sensor::new(0, Health::new(Duration::from_secs(95), 3)),
+        }
+    }
+
+    pub fn readings(&self) -> Readings {
+        Readings {
+            temperature: self.temperature.reading(),
+            pressure: self.pressure.reading(),
+            weight: self.weight.reading(),
+            ambient: self.ambient.reading(),
+            level: self.level.reading(),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
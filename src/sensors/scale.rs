@@ -1,3 +1,8 @@
+use crate::schemas::event::EventBuffer;
+use crate::sensors::cup::{CupDetector, CupEvent};
+use crate::sensors::flow::{DripDetector, FlowEstimator};
+use crate::sensors::health::Quality;
+use crate::sensors::reading::Sensor;
 use crate::{config::LoadCell as Config, types::Grams};
 use anyhow::Result;
 use esp_idf_svc::hal::{
@@ -6,12 +11,23 @@ use esp_idf_svc::hal::{
     peripheral::Peripheral,
 };
 use loadcell::{hx711::HX711, LoadCell};
+use std::ops::RangeInclusive;
 use std::sync::{
     mpsc::{channel, Sender},
-    Arc, RwLock,
+    Arc, Mutex, RwLock,
 };
 use std::time::{Duration, Instant};
 
+const VALID_RANGE: RangeInclusive<Grams> = -5000.0..=5000.0;
+
+const CALIBRATION_SAMPLES: usize = 16;
+const CALIBRATION_TIMEOUT: Duration = Duration::from_secs(10);
+// Noise and drift allowed during calibration, as a fraction of the reference load
+const CALIBRATION_TOLERANCE: f32 = 0.005;
+const MIN_REFERENCE_WEIGHT: Grams = 10.0;
+
+const AUTO_TARE_SAMPLES: usize = 16;
+
 pub type LoadSensor<'a, SckPin, DtPin> =
     HX711<PinDriver<'a, SckPin, Output>, PinDriver<'a, DtPin, Input>, Ets>;
 
@@ -20,29 +36,84 @@ pub struct ScaleConfig {
     pub scaling: f32,
 }
 
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case", tag = "step")]
+pub enum CalibrationStep {
+    Zero,
+    Reference { weight: Grams },
+}
+
 pub enum Message {
     Tare(usize),
     Scale(f32),
     SetPollInterval(Duration),
     SetFilterWindow(usize),
+    Calibrate(CalibrationStep, Sender<Result<Option<f32>, String>>),
+    StartShot,
+    EndShot,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct RawSample {
+    mean: f32,
+    deviation: f32,
+    drift: f32,
+}
+
+impl RawSample {
+    fn from_readings(readings: &[i32]) -> Self {
+        let mean_of = |readings: &[i32]| {
+            readings.iter().map(|&r| r as f32).sum::<f32>() / readings.len() as f32
+        };
+        let mean = mean_of(readings);
+        let variance = readings
+            .iter()
+            .map(|&r| (r as f32 - mean).powi(2))
+            .sum::<f32>()
+            / readings.len() as f32;
+        let (first, second) = readings.split_at(readings.len() / 2);
+
+        RawSample {
+            mean,
+            deviation: variance.sqrt(),
+            drift: (mean_of(second) - mean_of(first)).abs(),
+        }
+    }
+
+    fn is_stable(&self, span: f32) -> bool {
+        self.deviation.max(self.drift) <= span.abs() * CALIBRATION_TOLERANCE
+    }
 }
 
 #[derive(Clone)]
 pub struct Interface {
     pub mailbox: Sender<Message>,
-    pub weight: Arc<RwLock<Grams>>,
     pub flow: Arc<RwLock<f32>>,
+    pub shot_started: Arc<RwLock<Option<Instant>>>,
+    pub cup_removed: Arc<RwLock<bool>>,
 }
 
 impl Interface {
-    pub fn get_weight(&self) -> f32 {
-        *self.weight.read().unwrap()
+    // Stands in for a scale the board doesn't have, every message is dropped
+    pub fn absent() -> Self {
+        let (mailbox, _) = channel();
+        Self {
+            mailbox,
+            flow: Arc::new(RwLock::new(0.0)),
+            shot_started: Arc::new(RwLock::new(None)),
+            cup_removed: Arc::new(RwLock::new(false)),
+        }
     }
 
     pub fn get_flow(&self) -> f32 {
         *self.flow.read().unwrap()
     }
 
+    // When the first drip reached the cup during the current shot
+    pub fn get_shot_started(&self) -> Option<Instant> {
+        *self.shot_started.read().unwrap()
+    }
+
     pub fn tare(&self, times: usize) {
         let _ = self.mailbox.send(Message::Tare(times));
     }
@@ -59,15 +130,28 @@ impl Interface {
         let _ = self.mailbox.send(Message::SetFilterWindow(samples));
     }
 
+    // Zero with the platform empty, then place a known weight. The reference step returns
+    // the new scaling, which is applied straight away but left to the caller to persist
+    pub fn calibrate(&self, step: CalibrationStep) -> Result<Option<f32>, String> {
+        let (tx, rx) = channel();
+        self.mailbox
+            .send(Message::Calibrate(step, tx))
+            .map_err(|_| "Scale is not running".to_string())?;
+        rx.recv_timeout(CALIBRATION_TIMEOUT * 2)
+            .map_err(|_| "Timed out waiting for the scale".to_string())?
+    }
+
     pub fn start_brew(&self) {
         self.set_filter_window(10);
         self.set_poll_interval(Duration::from_millis(50));
-        self.tare(32)
+        self.tare(32);
+        let _ = self.mailbox.send(Message::StartShot);
     }
 
     pub fn stop_brewing(&self) {
         self.set_filter_window(8);
         self.set_poll_interval(Duration::from_millis(250));
+        let _ = self.mailbox.send(Message::EndShot);
     }
 }
 
@@ -82,6 +166,13 @@ where
     samples: Vec<(Instant, f32)>,
     samples_to_average: usize,
     interface: Interface,
+    weight: Sensor<Grams>,
+    calibration_zero: Option<RawSample>,
+    flow: FlowEstimator,
+    drip: DripDetector,
+    cup: CupDetector,
+    in_shot: bool,
+    events: Arc<Mutex<EventBuffer>>,
 }
 
 impl<'a, SckPin, DtPin> Scale<'a, SckPin, DtPin>
@@ -94,35 +185,130 @@ where
     }
 
     fn tare(&mut self, times: usize) {
+        self.samples.clear();
+        self.flow.reset();
+        self.cup.reset();
         self.load_sensor.tare(times);
     }
 
+    // The filtered weight, only when the HX711 had a new conversion ready
     fn read(&mut self) -> Option<f32> {
-        if let Ok(reading) = self.load_sensor.read_scaled() {
-            self.samples.push((Instant::now(), reading));
-            if self.samples.len() > self.samples_to_average {
-                self.samples
-                    .drain(0..(self.samples.len() - self.samples_to_average));
+        let reading = self.load_sensor.read_scaled().ok()?;
+        if !VALID_RANGE.contains(&reading) {
+            self.weight.failure(Quality::OutOfRange);
+            return None;
+        }
+        self.samples.push((Instant::now(), reading));
+        if self.samples.len() > self.samples_to_average {
+            self.samples
+                .drain(0..(self.samples.len() - self.samples_to_average));
+        }
+        Some(self.samples.iter().map(|(_, m)| m).sum::<f32>() / self.samples.len() as f32)
+    }
+
+    fn read_raw(&mut self) -> Result<RawSample, String> {
+        let deadline = Instant::now() + CALIBRATION_TIMEOUT;
+        let mut readings = Vec::with_capacity(CALIBRATION_SAMPLES);
+        while readings.len() < CALIBRATION_SAMPLES {
+            if Instant::now() > deadline {
+                return Err("Timed out reading the load cell".to_string());
+            }
+            match self.load_sensor.read() {
+                Ok(reading) => readings.push(reading),
+                Err(_) => std::thread::sleep(Duration::from_millis(10)),
             }
         }
-        if self.samples.is_empty() {
-            None
-        } else {
-            Some(self.samples.iter().map(|(_, m)| m).sum::<f32>() / self.samples.len() as f32)
+        Ok(RawSample::from_readings(&readings))
+    }
+
+    fn calibrate(&mut self, step: CalibrationStep) -> Result<Option<f32>, String> {
+        match step {
+            CalibrationStep::Zero => {
+                let zero = self.read_raw()?;
+                log::info!(
+                    "Load cell zero: {:.0} (deviation {:.1}, drift {:.1})",
+                    zero.mean,
+                    zero.deviation,
+                    zero.drift
+                );
+                self.calibration_zero = Some(zero);
+                self.tare(CALIBRATION_SAMPLES);
+                Ok(None)
+            }
+            CalibrationStep::Reference { weight } => {
+                if weight < MIN_REFERENCE_WEIGHT {
+                    return Err(format!(
+                        "Reference weight must be at least {}g",
+                        MIN_REFERENCE_WEIGHT
+                    ));
+                }
+                let zero = self
+                    .calibration_zero
+                    .ok_or("Zero the scale before placing the reference weight".to_string())?;
+                let loaded = self.read_raw()?;
+                let span = loaded.mean - zero.mean;
+
+                if !zero.is_stable(span) || !loaded.is_stable(span) {
+                    return Err(format!(
+                        "Readings are unstable (span {:.0}, deviation {:.1}/{:.1}, drift {:.1}/{:.1}), keep the scale still and try again",
+                        span, zero.deviation, loaded.deviation, zero.drift, loaded.drift
+                    ));
+                }
+
+                let scaling = weight / span;
+                if !scaling.is_finite() || scaling <= 0.0 {
+                    return Err(format!(
+                        "Reference reading {:.0} gives an invalid scaling",
+                        span
+                    ));
+                }
+
+                log::info!("Load cell scaling calibrated to {}", scaling);
+                self.calibration_zero = None;
+                self.load_sensor.set_scale(scaling);
+                Ok(Some(scaling))
+            }
         }
     }
 
-    fn estimate_flow(&self) {
-        let samples = &self.samples;
-        if samples.len() < self.samples_to_average {
-            *self.interface.flow.write().unwrap() = 0.0;
+    fn estimate_flow(&mut self, weight: Grams) {
+        let now = Instant::now();
+        let flow = self.flow.update(now, weight);
+        *self.interface.flow.write().unwrap() = flow;
+
+        if let Some(started) = self.drip.update(now, weight, flow) {
+            log::info!("First drip detected");
+            *self.interface.shot_started.write().unwrap() = Some(started);
         }
+    }
 
-        let (first, last) = (samples.first().unwrap(), samples.last().unwrap());
-        let time = last.0 - first.0;
-        let weight = last.1 - first.1;
+    fn detect_cup(&mut self, weight: Grams) {
+        let Some(event) = self.cup.update(Instant::now(), weight) else {
+            return;
+        };
+        log::info!("{}", event);
 
-        *self.interface.flow.write().unwrap() = weight / time.as_secs_f32();
+        let mut events = self.events.lock().unwrap();
+        match event {
+            CupEvent::Placed(_) => {
+                *self.interface.cup_removed.write().unwrap() = false;
+                events.info(module_path!(), event.to_string());
+                if !self.in_shot {
+                    drop(events);
+                    self.tare(AUTO_TARE_SAMPLES);
+                }
+            }
+            CupEvent::Removed(_) if self.in_shot => {
+                *self.interface.cup_removed.write().unwrap() = true;
+                events.warn(
+                    module_path!(),
+                    format!("{} during a shot, pausing yield", event),
+                );
+            }
+            CupEvent::Removed(_) | CupEvent::LiquidAdded(_) => {
+                events.info(module_path!(), event.to_string());
+            }
+        }
     }
 
     fn poll(&mut self) -> Duration {
@@ -131,15 +317,22 @@ where
         }
 
         if let Some(reading) = self.read() {
-            *self.interface.weight.write().unwrap() = reading;
-            self.estimate_flow();
+            self.weight.update(reading);
+            self.estimate_flow(reading);
+            self.detect_cup(reading);
         }
 
         self.next_poll = Instant::now() + self.poll_interval;
         self.poll_interval
     }
 
-    pub fn start(clock_pin: SckPin, data_pin: DtPin, config: &Config) -> Result<Interface> {
+    pub fn start(
+        clock_pin: SckPin,
+        data_pin: DtPin,
+        config: &Config,
+        weight: Sensor<Grams>,
+        events: Arc<Mutex<EventBuffer>>,
+    ) -> Result<Interface> {
         let dt = PinDriver::input(data_pin)?;
         let sck = PinDriver::output(clock_pin)?;
         let mut load_sensor = HX711::new(sck, dt, Ets);
@@ -148,8 +341,9 @@ where
 
         let interface = Interface {
             mailbox: tx,
-            weight: Arc::new(RwLock::new(0.0)),
             flow: Arc::new(RwLock::new(0.0)),
+            shot_started: Arc::new(RwLock::new(None)),
+            cup_removed: Arc::new(RwLock::new(false)),
         };
 
         load_sensor.set_scale(config.scaling);
@@ -161,6 +355,13 @@ where
             samples: Vec::new(),
             samples_to_average: config.window,
             interface: interface.clone(),
+            weight,
+            calibration_zero: None,
+            flow: FlowEstimator::default(),
+            drip: DripDetector::default(),
+            cup: CupDetector::default(),
+            in_shot: false,
+            events,
         };
 
         std::thread::Builder::new()
@@ -175,12 +376,10 @@ where
                 loop {
                     while let Ok(message) = rx.try_recv() {
                         match message {
-                            Message::Tare(times) => {
-                                loadcell.samples.clear();
-                                loadcell.tare(times);
-                            }
+                            Message::Tare(times) => loadcell.tare(times),
                             Message::Scale(scaling) => {
                                 loadcell.samples.clear();
+                                loadcell.flow.reset();
                                 loadcell.load_sensor.set_scale(scaling);
                             }
                             Message::SetPollInterval(duration) => {
@@ -190,6 +389,21 @@ where
                             Message::SetFilterWindow(samples) => {
                                 loadcell.samples_to_average = samples;
                             }
+                            Message::Calibrate(step, reply) => {
+                                loadcell.samples.clear();
+                                loadcell.flow.reset();
+                                let _ = reply.send(loadcell.calibrate(step));
+                            }
+                            Message::StartShot => {
+                                *loadcell.interface.shot_started.write().unwrap() = None;
+                                *loadcell.interface.cup_removed.write().unwrap() = false;
+                                loadcell.in_shot = true;
+                                loadcell.drip.arm();
+                            }
+                            Message::EndShot => {
+                                loadcell.in_shot = false;
+                                loadcell.drip.disarm();
+                            }
                         }
                     }
 
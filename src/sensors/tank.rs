@@ -0,0 +1,100 @@
+use crate::types::{Milliliters, Millimeters};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_LOOKUP_POINTS: usize = 8;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    // Straight sided tank, cross-section in mm²
+    Prismatic { cross_section: f32 },
+    // Water height to volume, for tanks that narrow or have a shaped base
+    Lookup([Option<(Millimeters, Milliliters)>; MAX_LOOKUP_POINTS]),
+}
+
+// The level sensor looks down from `mount_height` above the bottom of the tank
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub mount_height: Millimeters,
+    pub max_height: Millimeters,
+    pub shape: Shape,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        const MOUNT_HEIGHT: Millimeters = 200;
+        const MAX_HEIGHT: Millimeters = 160;
+        const CROSS_SECTION: f32 = 150.0 * 100.0;
+        Geometry {
+            mount_height: MOUNT_HEIGHT,
+            max_height: MAX_HEIGHT,
+            shape: Shape::Prismatic {
+                cross_section: CROSS_SECTION,
+            },
+        }
+    }
+}
+
+impl Geometry {
+    pub fn water_height(&self, distance: Millimeters) -> Millimeters {
+        self.mount_height
+            .saturating_sub(distance)
+            .min(self.max_height)
+    }
+
+    pub fn volume(&self, distance: Millimeters) -> Milliliters {
+        self.volume_at_height(self.water_height(distance))
+    }
+
+    pub fn capacity(&self) -> Milliliters {
+        self.volume_at_height(self.max_height)
+    }
+
+    pub fn percentage(&self, distance: Millimeters) -> f32 {
+        let capacity = self.capacity();
+        if capacity <= 0.0 {
+            return 0.0;
+        }
+        (self.volume(distance) / capacity * 100.0).clamp(0.0, 100.0)
+    }
+
+    fn volume_at_height(&self, height: Millimeters) -> Milliliters {
+        match self.shape {
+            // mm³ to mL
+            Shape::Prismatic { cross_section } => cross_section * height as f32 / 1000.0,
+            Shape::Lookup(points) => {
+                let mut points: Vec<(Millimeters, Milliliters)> =
+                    points.iter().flatten().copied().collect();
+                points.sort_by_key(|(h, _)| *h);
+                // A repeated height has no width to interpolate across, keep the first
+                points.dedup_by_key(|(h, _)| *h);
+                interpolate(&points, height)
+            }
+        }
+    }
+}
+
+fn interpolate(points: &[(Millimeters, Milliliters)], height: Millimeters) -> Milliliters {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return 0.0;
+    };
+    if height <= first.0 {
+        // Assume the tank is empty at zero height
+        return if first.0 == 0 {
+            first.1
+        } else {
+            first.1 * height as f32 / first.0 as f32
+        };
+    }
+    if height >= last.0 {
+        return last.1;
+    }
+
+    points
+        .windows(2)
+        .find(|pair| pair[0].0 < pair[1].0 && height <= pair[1].0)
+        .map(|pair| {
+            let ((h0, v0), (h1, v1)) = (pair[0], pair[1]);
+            v0 + (v1 - v0) * (height - h0) as f32 / (h1 - h0) as f32
+        })
+        .unwrap_or(last.1)
+}
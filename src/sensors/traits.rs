@@ -1,5 +1,14 @@
+use crate::sensors::health::Quality;
+
 pub trait TemperatureProbe {
     fn convert_voltage_to_degrees(&self, voltage: f64) -> Result<f32, String>;
+
+    // The RTD resistance behind a raw reading, before any calibration
+    fn resistance(&self, voltage: f64) -> f64;
+
+    fn diagnose(&self, _voltage: f64) -> Quality {
+        Quality::Good
+    }
 }
 pub trait PressureProbe {
     fn convert_voltage_to_pressure(&self, voltage: f64) -> Result<f32, String>;
@@ -1,6 +1,8 @@
 use super::traits::*;
 use super::FsmError as Error;
+use crate::components::descale::Progress as DescaleProgress;
 use crate::schemas::status::Operation as OperationReport;
+use crate::types::Grams;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
@@ -9,8 +11,10 @@ pub enum OperationalState {
     AutoTuneInit,
     AutoTuning,
     Idle,
-    Brewing,
+    // The yield the shot stops at, None for shots that run on time
+    Brewing(Option<Grams>),
     Steaming,
+    Descaling(DescaleProgress),
 }
 
 impl std::fmt::Display for OperationalState {
@@ -22,8 +26,9 @@ impl std::fmt::Display for OperationalState {
             }
             OperationalState::AutoTuneInit => write!(f, "Initialising auto-tune"),
             OperationalState::Idle => write!(f, "Idle"),
-            OperationalState::Brewing => write!(f, "Brewing"),
+            OperationalState::Brewing(_) => write!(f, "Brewing"),
             OperationalState::Steaming => write!(f, "Steaming"),
+            OperationalState::Descaling(progress) => write!(f, "Descaling: {}", progress.step),
         }
     }
 }
@@ -33,8 +38,10 @@ pub enum Transitions {
     Idle,
     StartAutoTune,
     AutoTuneComplete,
-    StartBrewing,
+    StartBrewing(Option<Grams>),
     StartSteaming,
+    StartDescale(DescaleProgress),
+    DescaleProgress(DescaleProgress),
     Stop,
 }
 
@@ -82,14 +89,59 @@ impl OperationalState {
                 None,
             )),
 
+            (OperationalState::Idle, Transitions::StartDescale(progress)) => {
+                *self = OperationalState::Descaling(progress.clone());
+                Ok(())
+            }
+            (_, Transitions::StartDescale(_)) => Err(Error::InvalidStateTransition(
+                "Descaling can only be started from idle".to_string(),
+            )),
+            (OperationalState::Descaling(_), Transitions::DescaleProgress(progress)) => {
+                *self = OperationalState::Descaling(progress.clone());
+                Ok(())
+            }
+            (OperationalState::Descaling(_), Transitions::Stop) => {
+                *self = OperationalState::Idle;
+                Ok(())
+            }
+            (OperationalState::Descaling(_), _) => {
+                Err(Error::Busy("System is busy descaling".to_string(), None))
+            }
+
+            (
+                OperationalState::Idle | OperationalState::Brewing(_) | OperationalState::Steaming,
+                Transitions::StartBrewing(target),
+            ) => {
+                *self = OperationalState::Brewing(*target);
+                Ok(())
+            }
+            (
+                OperationalState::Idle | OperationalState::Brewing(_) | OperationalState::Steaming,
+                Transitions::StartSteaming,
+            ) => {
+                *self = OperationalState::Steaming;
+                Ok(())
+            }
+            (
+                OperationalState::Idle | OperationalState::Brewing(_) | OperationalState::Steaming,
+                Transitions::Stop,
+            ) => {
+                *self = OperationalState::Idle;
+                Ok(())
+            }
+
             (_, _) => Err(Error::NotYetImplemented),
         }
     }
 
     pub fn to_report(&self) -> OperationReport {
+        let attributes = match self {
+            OperationalState::Descaling(progress) => serde_json::to_value(progress).ok(),
+            _ => None,
+        };
         OperationReport {
             state: self.to_string(),
-            attributes: None,
+            attributes,
         }
     }
 }
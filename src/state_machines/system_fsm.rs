@@ -107,6 +107,16 @@ impl SystemState {
             /* --------------------------- */
             (_, Transition::Reboot(delay)) => Ok(SystemState::Rebooting(Instant::now() + *delay)),
 
+            /* ------------------------ */
+            /* --- Warning Handling --- */
+            /* ------------------------ */
+            (SystemState::Healthy | SystemState::Warning(_), Transition::Warning(message)) => {
+                Ok(SystemState::Warning(message.clone()))
+            }
+            (SystemState::Healthy | SystemState::Warning(_), Transition::ClearWarnings) => {
+                Ok(SystemState::Healthy)
+            }
+
             /* --------------------------- */
             /* --- Unhandled Transitions --- */
             /* --------------------------- */
@@ -1,6 +1 @@
-pub type Bar = f32;
-pub type Temperature = f32;
-pub type Watts = f32;
-pub type Grams = f32;
-pub type Degrees = f32;
-pub type Millimeters = u16;
+pub use rs_coffee_core::types::*;